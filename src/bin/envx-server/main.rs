@@ -0,0 +1,53 @@
+//! Self-hostable stand-in for the envx API
+//!
+//! Serves every endpoint `envx` calls from a JSON data file. Point the CLI at it with
+//! `DEV_MODE=1` (localhost:3000) or by setting `sdk_url` in the config.
+
+use anyhow::{Context, Result};
+use clap::Parser;
+use envx::server::{
+    routes,
+    store::{self, Store},
+};
+use std::{net::SocketAddr, path::PathBuf, sync::Arc};
+
+/// Serve the envx API from a local data file
+#[derive(Parser)]
+#[clap(author, version, about)]
+struct Args {
+    /// Address to listen on
+    #[clap(long, default_value = "127.0.0.1")]
+    host: String,
+
+    /// Port to listen on
+    #[clap(short, long, default_value_t = 3000)]
+    port: u16,
+
+    /// Data file, defaults to ~/.config/envcli/server/data.json
+    #[clap(short, long)]
+    data: Option<PathBuf>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let path = match args.data {
+        Some(path) => path,
+        None => store::get_default_location()?,
+    };
+    let store = Arc::new(Store::open(path.clone())?);
+
+    let address = format!("{}:{}", args.host, args.port)
+        .parse::<SocketAddr>()
+        .context("Invalid address")?;
+
+    println!("Serving {} on http://{}", path.display(), address);
+
+    axum::Server::bind(&address)
+        .serve(routes::router(store).into_make_service())
+        .await
+        .context("Server error")?;
+
+    Ok(())
+}
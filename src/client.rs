@@ -0,0 +1,427 @@
+use crate::{
+    crypto::{decrypt, encrypt_multi},
+    error::{Error, Result},
+    http::Http,
+    kvpair::KVPair,
+    partial_variable::{DeDupe, ParsedPartialVariable, PartialVariable, ToKVPair, ToParsed},
+    token_cache::TokenCache,
+    types::ProjectInfo,
+};
+use chrono::Utc;
+use pgp::{
+    composed::message::Message, crypto::hash::HashAlgorithm, types::KeyTrait, Deserializable,
+    SignedPublicKey, SignedSecretKey,
+};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use reqwest::{header, Method, Response};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::{Arc, OnceLock};
+use url::Url;
+
+/// Called with the fingerprint of the key, returns its passphrase
+pub type PassphraseCallback = Arc<dyn Fn(&str) -> Result<String> + Send + Sync>;
+
+/// Where the passphrase of the secret key comes from
+///
+/// It is only resolved once per `Client`, the first time the key is used
+#[derive(Clone)]
+pub enum Passphrase {
+    /// The key isn't protected by a passphrase
+    None,
+    Static(String),
+    /// Name of an environment variable holding the passphrase
+    Env(String),
+    /// E.g. to read a secret store or prompt for it
+    Callback(PassphraseCallback),
+}
+
+/// A user and their secret key, used to authenticate and to decrypt
+#[derive(Clone)]
+pub struct Identity {
+    /// ID the key was uploaded as, see `envx upload`
+    pub user_id: String,
+    pub secret_key: SignedSecretKey,
+}
+
+impl Identity {
+    pub fn new(user_id: impl Into<String>, armored_secret_key: &str) -> Result<Self> {
+        let (secret_key, _) = SignedSecretKey::from_string(armored_secret_key)
+            .map_err(|e| Error::InvalidKey(format!("Secret key: {}", e)))?;
+
+        Ok(Self {
+            user_id: user_id.into(),
+            secret_key,
+        })
+    }
+
+    pub fn fingerprint(&self) -> String {
+        hex::encode(self.secret_key.fingerprint())
+    }
+}
+
+/// Client for the envx API
+///
+/// Everything it needs is injected, nothing is read from the envx config
+pub struct Client {
+    http: Http,
+    api_url: Url,
+    identity: Identity,
+    passphrase: Passphrase,
+    resolved_passphrase: OnceLock<String>,
+    tokens: TokenCache,
+}
+
+impl Client {
+    pub fn new(api_url: &str, identity: Identity, passphrase: Passphrase) -> Result<Self> {
+        let api_url = Url::parse(api_url)
+            .map_err(|e| Error::Config(format!("Invalid API URL {}: {}", api_url, e)))?;
+
+        Ok(Self {
+            http: Http::default(),
+            api_url,
+            identity,
+            passphrase,
+            resolved_passphrase: OnceLock::new(),
+            tokens: TokenCache::default(),
+        })
+    }
+
+    /// Share a connection pool and retry policy with other clients
+    pub fn with_http(mut self, http: Http) -> Self {
+        self.http = http;
+        self
+    }
+
+    /// Share signed tokens with other clients, or with other processes if the cache has a file
+    pub fn with_token_cache(mut self, tokens: TokenCache) -> Self {
+        self.tokens = tokens;
+        self
+    }
+
+    pub fn identity(&self) -> &Identity {
+        &self.identity
+    }
+
+    /// Register a public key, returns the ID of the new user
+    pub async fn new_user(
+        http: &Http,
+        api_url: &str,
+        username: &str,
+        public_key: &str,
+    ) -> Result<String> {
+        let url = Url::parse(api_url)
+            .and_then(|u| u.join("/user/new"))
+            .map_err(|e| Error::Config(format!("Invalid API URL {}: {}", api_url, e)))?;
+
+        let body = json!({
+            "username": username,
+            "public_key": public_key
+        });
+
+        let request = http.request(Method::POST, url).json(&body);
+        Ok(http.send(request).await?.text().await?)
+    }
+
+    pub async fn test_auth(&self) -> Result<String> {
+        Ok(self
+            .send(Method::POST, "/test-auth", None)
+            .await?
+            .text()
+            .await?)
+    }
+
+    pub async fn project_info(&self, project_id: &str) -> Result<ProjectInfo> {
+        let res = self
+            .send(Method::GET, &format!("/project/{}", project_id), None)
+            .await?;
+        parse(res).await
+    }
+
+    /// Encrypt the variables to every member of the project and upload them
+    pub async fn set_many(&self, kvpairs: &[KVPair], project_id: &str) -> Result<Vec<String>> {
+        let project_info = self.project_info(project_id).await?;
+
+        let pubkeys = project_info
+            .users
+            .iter()
+            .map(|u| {
+                SignedPublicKey::from_string(&u.public_key)
+                    .map(|(k, _)| k)
+                    .map_err(|e| Error::InvalidKey(format!("Public key of {}: {}", u.id, e)))
+            })
+            .collect::<Result<Vec<SignedPublicKey>>>()?;
+
+        let messages = kvpairs
+            .par_iter()
+            .map(|k| encrypt_multi(&k.to_json()?, &pubkeys))
+            .collect::<Result<Vec<String>>>()?;
+
+        #[derive(Serialize, Deserialize, Debug)]
+        struct SetManyVariableReturnType {
+            id: String,
+        }
+
+        let body = json!({
+            "project_id": project_id,
+            "variables": messages,
+        });
+
+        let res = self
+            .send(Method::POST, "/variables/set-many", Some(body))
+            .await?;
+
+        Ok(parse::<Vec<SetManyVariableReturnType>>(res)
+            .await?
+            .into_iter()
+            .map(|r| r.id)
+            .collect())
+    }
+
+    /// Still encrypted variables of every project the user is a member of
+    pub async fn all_variables_encrypted(&self) -> Result<Vec<PartialVariable>> {
+        let path = format!("/user/{}/variables", self.identity.user_id);
+        parse(self.send(Method::GET, &path, None).await?).await
+    }
+
+    /// Still encrypted variables of a project, every version of every key
+    pub async fn variables_encrypted(&self, project_id: &str) -> Result<Vec<PartialVariable>> {
+        let path = format!("/project/{}/variables", project_id);
+        parse(self.send(Method::GET, &path, None).await?).await
+    }
+
+    /// Every version of every variable of a project
+    pub async fn variables(&self, project_id: &str) -> Result<Vec<ParsedPartialVariable>> {
+        let encrypted = self.variables_encrypted(project_id).await?;
+        let messages = encrypted
+            .iter()
+            .map(|e| e.value.clone())
+            .collect::<Vec<String>>();
+
+        Ok(encrypted.zip_to_parsed(self.decrypt(&messages)?))
+    }
+
+    /// Newest version of every variable of a project, sorted by key
+    pub async fn variables_pruned(&self, project_id: &str) -> Result<Vec<KVPair>> {
+        let mut pruned = self.variables(project_id).await?.dedupe().to_kvpair();
+        pruned.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(pruned)
+    }
+
+    /// Decrypt armored messages holding serialized `KVPair`s
+    pub fn decrypt(&self, messages: &[String]) -> Result<Vec<KVPair>> {
+        if messages.is_empty() {
+            return Ok(vec![]);
+        }
+        let passphrase = self.passphrase()?;
+
+        messages
+            .par_iter()
+            .map(|m| {
+                decrypt(m, &self.identity.secret_key, passphrase.clone())
+                    .and_then(|d| KVPair::from_json(&d))
+            })
+            .collect()
+    }
+
+    /// Returns the ID and armored public key of a user
+    pub async fn get_user(&self, user_id: &str) -> Result<(String, String)> {
+        #[derive(Serialize, Deserialize, Debug)]
+        struct StrippedUser {
+            id: String,
+            public_key: String,
+        }
+
+        let res = self
+            .send(Method::GET, &format!("/user/{}", user_id), None)
+            .await?;
+        let user = parse::<StrippedUser>(res).await?;
+
+        Ok((user.id, user.public_key))
+    }
+
+    /// Delete the user of this client
+    pub async fn delete_user(&self) -> Result<()> {
+        let path = format!("/user/{}", self.identity.user_id);
+        self.send(Method::DELETE, &path, None).await?;
+        Ok(())
+    }
+
+    pub async fn add_user_to_project(&self, user_id: &str, project_id: &str) -> Result<()> {
+        let path = format!("/project/{}/add-user", project_id);
+        let body = json!({ "user_id": user_id });
+        self.send(Method::POST, &path, Some(body)).await?;
+        Ok(())
+    }
+
+    pub async fn remove_user_from_project(&self, user_id: &str, project_id: &str) -> Result<()> {
+        let path = format!("/project/{}/remove-user", project_id);
+        let body = json!({ "user_id": user_id });
+        self.send(Method::POST, &path, Some(body)).await?;
+        Ok(())
+    }
+
+    /// Replace the encrypted values of existing variables, keeping their IDs
+    pub async fn update_many(&self, partials: &[PartialVariable]) -> Result<Vec<String>> {
+        let body = json!({ "variables": partials });
+        let res = self
+            .send(Method::POST, "/variables/update-many", Some(body))
+            .await?;
+        parse(res).await
+    }
+
+    pub async fn list_projects(&self) -> Result<Vec<String>> {
+        parse(self.send(Method::GET, "/projects", None).await?).await
+    }
+
+    /// Returns the ID of the new project
+    pub async fn new_project(&self) -> Result<String> {
+        Ok(self
+            .send(Method::POST, "/projects/new", None)
+            .await?
+            .text()
+            .await?)
+    }
+
+    pub async fn delete_project(&self, project_id: &str) -> Result<()> {
+        let path = format!("/project/{}", project_id);
+        self.send(Method::DELETE, &path, None).await?;
+        Ok(())
+    }
+
+    pub async fn delete_variable(&self, variable_id: &str) -> Result<()> {
+        let path = format!("/variables/{}", variable_id);
+        self.send(Method::DELETE, &path, None).await?;
+        Ok(())
+    }
+
+    /// Bearer token proving the request comes from the owner of the key
+    ///
+    /// Reused from the token cache while it's valid
+    pub fn token(&self) -> Result<String> {
+        Ok(match self.cached_token() {
+            Some(token) => token,
+            None => self.sign_new_token()?,
+        })
+    }
+
+    fn cached_token(&self) -> Option<String> {
+        self.tokens.get(
+            &self.identity.fingerprint(),
+            self.api_url.as_str(),
+            &self.identity.user_id,
+        )
+    }
+
+    fn sign_new_token(&self) -> Result<String> {
+        let token = sign_token(
+            &self.identity.secret_key,
+            self.passphrase()?,
+            &self.identity.user_id,
+        )?;
+        self.tokens.insert(
+            &self.identity.fingerprint(),
+            self.api_url.as_str(),
+            &self.identity.user_id,
+            &token,
+        );
+        Ok(token)
+    }
+
+    fn passphrase(&self) -> Result<String> {
+        if let Some(passphrase) = self.resolved_passphrase.get() {
+            return Ok(passphrase.clone());
+        }
+
+        let passphrase = match &self.passphrase {
+            Passphrase::None => String::new(),
+            Passphrase::Static(p) => p.clone(),
+            Passphrase::Env(name) => std::env::var(name)
+                .map_err(|_| Error::Config(format!("Environment variable {} is not set", name)))?,
+            Passphrase::Callback(callback) => callback(&self.identity.fingerprint())?,
+        };
+
+        Ok(self.resolved_passphrase.get_or_init(|| passphrase).clone())
+    }
+
+    async fn send(&self, method: Method, path: &str, body: Option<Value>) -> Result<Response> {
+        let url = self
+            .api_url
+            .join(path)
+            .map_err(|e| Error::Config(format!("Invalid API path {}: {}", path, e)))?;
+
+        let cached = self.cached_token();
+        let token = match &cached {
+            Some(token) => token.clone(),
+            None => self.sign_new_token()?,
+        };
+
+        match self.send_with(&method, &url, body.as_ref(), &token).await {
+            // The server may have a shorter window than the cache, or a skewed clock
+            Err(Error::Unauthorized(_)) if cached.is_some() => {
+                self.tokens
+                    .invalidate(&self.identity.fingerprint(), self.api_url.as_str());
+                let token = self.sign_new_token()?;
+                self.send_with(&method, &url, body.as_ref(), &token).await
+            }
+            res => res,
+        }
+    }
+
+    async fn send_with(
+        &self,
+        method: &Method,
+        url: &Url,
+        body: Option<&Value>,
+        token: &str,
+    ) -> Result<Response> {
+        let mut request = self
+            .http
+            .request(method.clone(), url.clone())
+            .header(header::AUTHORIZATION, format!("Bearer {}", token));
+        if let Some(body) = body {
+            request = request.json(body);
+        }
+
+        self.http.send(request).await
+    }
+}
+
+/// Sign the current time with the key, the server checks it against the user's public key
+pub fn sign_token(
+    secret_key: &SignedSecretKey,
+    passphrase: String,
+    user_id: &str,
+) -> Result<String> {
+    let msg = Message::new_literal("none", &Utc::now().to_string());
+
+    let signature = msg
+        .sign(secret_key, || passphrase, HashAlgorithm::SHA3_512)
+        .map_err(|e| match e {
+            pgp::errors::Error::Incomplete(_) => Error::Unauthorized(
+                "Failed to sign the authentication token, the passphrase is most likely wrong"
+                    .into(),
+            ),
+            e => Error::Unauthorized(format!("Failed to sign the authentication token: {}", e)),
+        })?
+        .to_armored_string(None)
+        .map_err(|e| Error::Unauthorized(format!("Failed to armor the signature: {}", e)))?;
+
+    Ok(json!({
+        "token": user_id,
+        "signature": signature,
+    })
+    .to_string())
+}
+
+/// The body is read before it's decoded, so a dropped connection isn't reported as bad JSON
+async fn parse<T: DeserializeOwned>(res: Response) -> Result<T> {
+    let body = res.text().await?;
+    serde_json::from_str(&body).map_err(|e| {
+        let preview = body.chars().take(200).collect::<String>();
+        Error::Network(format!(
+            "Unexpected response from the server ({}): {}",
+            e, preview
+        ))
+    })
+}
@@ -1,16 +1,8 @@
 use super::*;
 use crate::{
-    sdk::{get_api_url, SDK},
-    utils::{
-        auth::get_token, choice::Choice, config::get_config, partial_variable::PartialVariable,
-        prompt::prompt_text, rpgp::encrypt_multi,
-    },
+    sdk::SDK,
+    utils::{choice::Choice, config::get_config, prompt::prompt_text},
 };
-use pgp::{Deserializable, SignedPublicKey};
-use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-use reqwest::header;
-use serde_json::json;
-use std::collections::HashSet;
 
 /// Add a user to a project
 #[derive(Parser)]
@@ -38,8 +30,8 @@ pub async fn command(args: Args) -> Result<()> {
     let config = get_config()?;
     let key = config.get_key_or_default(args.key)?;
 
-    let uuid = key
-        .uuid
+    config
+        .key_uuid(&key)?
         .context("Key does not have a UUID, try `envx upload`")?;
     let (_, public_key) = SDK::get_user(&key.fingerprint, &user_id)
         .await
@@ -49,8 +41,6 @@ pub async fn command(args: Args) -> Result<()> {
 
     let project_info = SDK::get_project_info(&project_id, &key.fingerprint).await?;
 
-    let (kvpairs, mut partials) = SDK::get_variables(&project_id, &key.fingerprint).await?;
-
     let mut recipients = project_info
         .users
         .iter()
@@ -59,48 +49,7 @@ pub async fn command(args: Args) -> Result<()> {
 
     recipients.push(public_key);
 
-    let recipients = recipients
-        .into_iter()
-        .collect::<HashSet<String>>()
-        .into_iter()
-        .collect::<Vec<String>>();
-
-    let pubkeys = recipients
-        .iter()
-        .map(|k| Ok(SignedPublicKey::from_string(k)?.0))
-        .collect::<Result<Vec<SignedPublicKey>>>()?;
-
-    let messages = kvpairs
-        .par_iter()
-        .map(|k| encrypt_multi(&k.to_json()?, &pubkeys))
-        .collect::<Result<Vec<String>>>()?;
-
-    let partials = partials
-        .iter_mut()
-        .zip(messages.iter())
-        .map(|(p, m)| {
-            p.value = m.into();
-            p
-        })
-        .collect::<Vec<&mut PartialVariable>>();
-
-    let body = json!({
-        "variables": partials,
-    });
-
-    let client = reqwest::Client::new();
-    let auth_token = get_token(&key.fingerprint, &uuid).await?;
-
-    let url = get_api_url().join("/variables/update-many")?;
-
-    let res = client
-        .post(url)
-        .header(header::AUTHORIZATION, format!("Bearer {}", auth_token))
-        .json(&body)
-        .send()
-        .await?
-        .json::<Vec<String>>()
-        .await?;
+    let res = SDK::reencrypt_variables(&project_id, &key.fingerprint, &recipients).await?;
 
     println!("Updated {} variables", res.len());
     println!("IDs: {:?}", res);
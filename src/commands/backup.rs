@@ -0,0 +1,41 @@
+use super::*;
+use crate::utils::{backup::Backup, config::get_config, prompt::prompt_password};
+use envx::file::write_private;
+use std::path::PathBuf;
+
+/// Save every key and the config to a file encrypted with a passphrase
+#[derive(Parser)]
+pub struct Args {
+    /// File to write the backup to
+    #[clap(short, long, default_value = "envx-backup.pgp")]
+    output: PathBuf,
+
+    /// Passphrase to encrypt the backup with
+    #[clap(short, long)]
+    passphrase: Option<String>,
+
+    /// Leave out the directories linked to projects, e.g. to restore on another machine
+    #[clap(long)]
+    no_paths: bool,
+}
+
+pub async fn command(args: Args) -> Result<()> {
+    let config = get_config().context("Failed to get config")?;
+    let backup = Backup::collect(config, !args.no_paths)?;
+
+    let passphrase = match args.passphrase {
+        Some(p) => p,
+        None => prompt_password("Backup passphrase")?,
+    };
+    let encrypted = backup.encrypt(passphrase)?;
+    write_private(&args.output, &encrypted)?;
+
+    println!(
+        "Backed up {} key(s) to {}",
+        backup.keys.len(),
+        args.output.display()
+    );
+    println!("Restore it with `envx restore {}`", args.output.display());
+
+    Ok(())
+}
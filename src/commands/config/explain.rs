@@ -0,0 +1,63 @@
+use super::*;
+use crate::utils::{
+    config::get_config,
+    resolve::{self, Resolved},
+    table::Table,
+};
+use std::collections::BTreeMap;
+
+/// Show the effective settings and where each of them comes from
+///
+/// Flags win over ENVX_* environment variables, which win over files and defaults
+#[derive(Parser)]
+pub struct Args {
+    /// Partial fingerprint of key to use
+    #[clap(short, long)]
+    key: Option<String>,
+
+    /// Project ID
+    #[clap(short, long)]
+    project_id: Option<String>,
+}
+
+pub async fn command(args: Args) -> Result<()> {
+    let config = get_config()?;
+    let mut rows = BTreeMap::new();
+
+    let mut row = |name: &str, value: String, source: String| {
+        rows.insert(name.to_string(), format!("{}\n{}", value, source.dimmed()));
+    };
+    let describe = |r: Resolved<String>| (r.value, r.source.to_string());
+
+    let dir = resolve::config_dir()?;
+    row(
+        "config dir",
+        dir.value.display().to_string(),
+        dir.source.to_string(),
+    );
+
+    let (value, source) = describe(resolve::profile());
+    row("profile", value, source);
+
+    let (value, source) = describe(resolve::api_url(&config)?);
+    row("api url", value, source);
+
+    let key = resolve::key(&config, args.key)?;
+    let value = match config.get_key(&key.value) {
+        Ok(k) if !key.value.is_empty() => k.to_string(),
+        _ => format!("{} (not found)", key.value),
+    };
+    row("key", value, key.source.to_string());
+
+    match resolve::project(&config, args.project_id)? {
+        Some(project) => {
+            let (value, source) = describe(project);
+            row("project", value, source)
+        }
+        None => row("project", "none".into(), "asked for when needed".into()),
+    }
+
+    Table::new("Effective configuration".into(), rows).print()?;
+
+    Ok(())
+}
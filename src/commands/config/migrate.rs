@@ -0,0 +1,28 @@
+use super::*;
+use envx::paths::{config_dir, ConfigDir};
+use std::fs;
+
+/// Move ~/.config/envcli to $XDG_CONFIG_HOME/envcli, with its keys and offline store
+#[derive(Parser)]
+pub struct Args {}
+
+pub async fn command(_args: Args) -> Result<()> {
+    let ConfigDir::Legacy { path, xdg } = config_dir()? else {
+        println!("Nothing to migrate");
+        return Ok(());
+    };
+
+    if let Some(parent) = xdg.parent() {
+        fs::create_dir_all(parent).context("Failed to create XDG_CONFIG_HOME")?;
+    }
+    fs::rename(&path, &xdg).with_context(|| {
+        format!(
+            "Failed to move {} to {}, move it by hand",
+            path.display(),
+            xdg.display()
+        )
+    })?;
+
+    println!("Moved {} to {}", path.display(), xdg.display());
+    Ok(())
+}
@@ -7,6 +7,9 @@ pub(super) use colored::Colorize;
 use crate::commands_enum;
 use clap::Subcommand;
 
+pub mod explain;
+pub mod migrate;
+pub mod profile;
 pub mod set;
 
 /// Delete a resource. (project, key)
@@ -16,7 +19,7 @@ pub struct Args {
     command: Commands,
 }
 
-commands_enum!(set);
+commands_enum!(set, profile, explain, migrate);
 
 pub async fn command(args: Args) -> Result<()> {
     Commands::exec(args).await?;
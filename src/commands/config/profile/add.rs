@@ -0,0 +1,55 @@
+use super::*;
+use crate::utils::config::{Config, Profile, DEFAULT_PROFILE};
+use url::Url;
+
+/// Add a server profile, or change the URL or default key of an existing one
+#[derive(Parser)]
+pub struct Args {
+    name: String,
+
+    /// URL of the server
+    #[clap(short, long)]
+    url: String,
+
+    /// Partial fingerprint of the key to use when none is given
+    #[clap(short, long)]
+    key: Option<String>,
+}
+
+pub async fn command(args: Args) -> Result<()> {
+    if args.name == DEFAULT_PROFILE {
+        anyhow::bail!(
+            "The {} profile uses `sdk_url` from the config",
+            DEFAULT_PROFILE
+        );
+    }
+    Url::parse(&args.url).with_context(|| format!("Invalid URL {}", args.url))?;
+
+    Config::update(|config| {
+        let default_key = match &args.key {
+            Some(key) => Some(config.get_key(key)?.fingerprint),
+            None => None,
+        };
+
+        let profile = config
+            .profiles
+            .entry(args.name.clone())
+            .or_insert_with(|| Profile {
+                url: args.url.clone(),
+                uuids: Default::default(),
+                default_key: None,
+            });
+        profile.url = args.url.clone();
+        if default_key.is_some() {
+            profile.default_key = default_key;
+        }
+        Ok(())
+    })?;
+
+    println!(
+        "Profile {} saved, upload a key to it with `envx --profile {} upload -k <key>`",
+        args.name, args.name
+    );
+
+    Ok(())
+}
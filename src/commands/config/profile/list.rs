@@ -0,0 +1,39 @@
+use super::*;
+use crate::{
+    constants::DEFAULT_API_URL,
+    utils::{
+        config::{get_config, DEFAULT_PROFILE},
+        resolve::active_profile,
+    },
+};
+
+/// List the server profiles and the keys uploaded to each of them
+#[derive(Parser)]
+pub struct Args {}
+
+pub async fn command(_args: Args) -> Result<()> {
+    let config = get_config()?;
+    let active = active_profile();
+
+    let default_url = config.sdk_url.clone().unwrap_or(DEFAULT_API_URL.into());
+    let uploaded = config.keys.iter().filter(|k| k.uuid.is_some()).count();
+    let profiles = std::iter::once((DEFAULT_PROFILE.to_string(), default_url, uploaded)).chain(
+        config
+            .profiles
+            .iter()
+            .map(|(name, p)| (name.clone(), p.url.clone(), p.uuids.len())),
+    );
+
+    for (name, url, uploaded) in profiles {
+        let marker = if name == active { "*" } else { " " };
+        println!(
+            "{} {} {} ({} key(s) uploaded)",
+            marker,
+            name.bold(),
+            url,
+            uploaded
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,26 @@
+#[allow(unused_imports)]
+pub(super) use anyhow::{anyhow, Context, Result};
+pub(super) use clap::Parser;
+#[allow(unused_imports)]
+pub(super) use colored::Colorize;
+
+use crate::commands_enum;
+use clap::Subcommand;
+
+pub mod add;
+pub mod list;
+pub mod remove;
+
+/// Manage server profiles, selected with --profile or ENVX_PROFILE
+#[derive(Parser)]
+pub struct Args {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+commands_enum!(add, list, remove);
+
+pub async fn command(args: Args) -> Result<()> {
+    Commands::exec(args).await?;
+    Ok(())
+}
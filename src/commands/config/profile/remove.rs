@@ -0,0 +1,17 @@
+use super::*;
+use crate::utils::config::Config;
+
+/// Remove a server profile, the users on its server are left as they are
+#[derive(Parser)]
+pub struct Args {
+    name: String,
+}
+
+pub async fn command(args: Args) -> Result<()> {
+    Config::update(|config| {
+        if config.profiles.remove(&args.name).is_none() {
+            anyhow::bail!("Profile {} not found", args.name);
+        }
+        Ok(())
+    })
+}
@@ -0,0 +1,27 @@
+use super::*;
+use crate::utils::config::{get_token_cache_path, Config};
+use envx::token_cache::TokenCache;
+
+/// Keep signed auth tokens on disk for a few minutes, so consecutive commands sign once
+#[derive(Parser)]
+pub struct Args {
+    #[clap(action = clap::ArgAction::Set)]
+    enabled: bool,
+}
+
+pub async fn command(args: Args) -> Result<()> {
+    Config::update(|config| {
+        let mut settings = config.get_settings()?;
+        settings.cache_tokens = args.enabled;
+        config.settings = Some(settings);
+        Ok(())
+    })?;
+
+    if !args.enabled {
+        TokenCache::default()
+            .persist_to(get_token_cache_path()?)
+            .clear()?;
+    }
+
+    Ok(())
+}
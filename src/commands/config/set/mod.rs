@@ -7,6 +7,9 @@ pub(super) use colored::Colorize;
 use crate::commands_enum;
 use clap::Subcommand;
 
+pub mod cache_tokens;
+pub mod network;
+pub mod online;
 pub mod primary_key;
 
 /// Delete a resource. (project, key)
@@ -16,7 +19,7 @@ pub struct Args {
     command: Commands,
 }
 
-commands_enum!(primary_key);
+commands_enum!(primary_key, online, network, cache_tokens);
 
 pub async fn command(args: Args) -> Result<()> {
     Commands::exec(args).await?;
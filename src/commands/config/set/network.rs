@@ -0,0 +1,44 @@
+use super::*;
+use crate::utils::config::Config;
+
+/// Set the timeouts and retries of requests to the server
+#[derive(Parser)]
+pub struct Args {
+    /// Seconds to wait for a connection
+    #[clap(long, value_parser = clap::value_parser!(u64).range(1..))]
+    connect_timeout: Option<u64>,
+
+    /// Seconds a whole request may take
+    #[clap(long, value_parser = clap::value_parser!(u64).range(1..))]
+    request_timeout: Option<u64>,
+
+    /// Retries of failed requests that are safe to repeat, 0 disables them
+    #[clap(long)]
+    retries: Option<u32>,
+}
+
+pub async fn command(args: Args) -> Result<()> {
+    let settings = Config::update(|config| {
+        let mut settings = config.get_settings()?;
+
+        if let Some(connect_timeout) = args.connect_timeout {
+            settings.connect_timeout = connect_timeout;
+        }
+        if let Some(request_timeout) = args.request_timeout {
+            settings.request_timeout = request_timeout;
+        }
+        if let Some(retries) = args.retries {
+            settings.max_retries = retries;
+        }
+
+        config.settings = Some(settings.clone());
+        Ok(settings)
+    })?;
+
+    println!(
+        "Connect timeout: {}s, request timeout: {}s, retries: {}",
+        settings.connect_timeout, settings.request_timeout, settings.max_retries
+    );
+
+    Ok(())
+}
@@ -0,0 +1,23 @@
+use super::*;
+use crate::utils::config::Config;
+
+/// Use the server (true) or the offline store (false)
+#[derive(Parser)]
+pub struct Args {
+    #[clap(action = clap::ArgAction::Set)]
+    online: bool,
+}
+
+pub async fn command(args: Args) -> Result<()> {
+    Config::update(|config| {
+        config.online = args.online;
+        Ok(())
+    })?;
+
+    if !args.online {
+        println!("Offline mode enabled, variables are read from and written to the local store");
+        println!("Run `envx sync` to reconcile it with the server");
+    }
+
+    Ok(())
+}
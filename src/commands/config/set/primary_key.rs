@@ -1,5 +1,8 @@
 use super::*;
-use crate::utils::{config::get_config, prompt::prompt_select};
+use crate::utils::{
+    config::{get_config, Config},
+    prompt::prompt_select,
+};
 
 #[derive(Parser)]
 pub struct Args {
@@ -9,15 +12,10 @@ pub struct Args {
 }
 
 pub async fn command(args: Args) -> Result<()> {
-    let mut config = get_config()?;
-
     let fingerprint = match args.key {
         Some(k) => k,
-        None => prompt_select("Select key to set as primary", config.keys.clone())?.fingerprint,
+        None => prompt_select("Select key to set as primary", get_config()?.keys)?.fingerprint,
     };
 
-    config.set_primary_key(&fingerprint)?;
-    config.write()?;
-
-    Ok(())
+    Config::update(|config| config.set_primary_key(&fingerprint))
 }
@@ -24,7 +24,7 @@ pub struct Args {
 // TODO: fix configuration race condition while deleting multiple keys
 
 pub async fn command(args: Args) -> Result<()> {
-    let mut config = crate::utils::config::get_config().context("Failed to get config")?;
+    let config = crate::utils::config::get_config().context("Failed to get config")?;
     let kl_arc = std::sync::Arc::new(&config.keys);
     let primary_key = &config.primary_key;
 
@@ -74,37 +74,44 @@ pub async fn command(args: Args) -> Result<()> {
 
     let keys = selected
         .iter()
-        .map(|it| find(it, &kl_arc).expect("Failed to find key (1)"))
-        .collect::<Vec<_>>();
+        .map(|it| {
+            let key = find(it, &kl_arc).expect("Failed to find key (1)");
+            let on_server = config.key_uuid(&key)?.is_some();
+            Ok((key, on_server))
+        })
+        .collect::<Result<Vec<_>>>()?;
 
     let tasks: Vec<_> = keys
         .into_iter()
-        .map(|item| -> tokio::task::JoinHandle<anyhow::Result<()>> {
-            tokio::spawn(async move {
-                let key_dir = crate::utils::rpgp::get_vault_location()?.join(&item.fingerprint);
-
-                if item.uuid.is_some() {
-                    println!("Deleting key {} on server...", &item);
-                    match SDK::delete_key(&item.fingerprint).await {
-                        Ok(_) => {}
-                        Err(e) => {
-                            println!("Failed to delete key on server: {}", e);
-                            bail!("Failed to delete key on server");
+        .map(
+            |(item, on_server)| -> tokio::task::JoinHandle<anyhow::Result<()>> {
+                tokio::spawn(async move {
+                    let key_dir = crate::utils::rpgp::get_vault_location()?.join(&item.fingerprint);
+
+                    if on_server {
+                        println!("Deleting key {} on server...", &item);
+                        match SDK::delete_key(&item.fingerprint).await {
+                            Ok(_) => {}
+                            Err(e) => {
+                                println!("Failed to delete key on server: {}", e);
+                                bail!("Failed to delete key on server");
+                            }
                         }
+                    } else {
+                        println!("Key {} not on server", item);
                     }
-                } else {
-                    println!("Key {} not on server", item);
-                }
-
-                if key_dir.exists() {
-                    std::fs::remove_dir_all(key_dir).context("Failed to delete key directory")?
-                } else {
-                    println!("Key {} not on disk", item);
-                }
-
-                Ok(())
-            })
-        })
+
+                    if key_dir.exists() {
+                        std::fs::remove_dir_all(key_dir)
+                            .context("Failed to delete key directory")?
+                    } else {
+                        println!("Key {} not on disk", item);
+                    }
+
+                    Ok(())
+                })
+            },
+        )
         .collect();
 
     let results = futures::future::join_all(tasks).await;
@@ -114,11 +121,14 @@ pub async fn command(args: Args) -> Result<()> {
             .context("Failed to delete key")?;
     }
 
-    config.keys.retain(|k| !&selected.contains(&k.fingerprint));
-
-    config.write().context("Failed to write config")?;
-
-    Ok(())
+    crate::utils::config::Config::update(|config| {
+        config.keys.retain(|k| !&selected.contains(&k.fingerprint));
+        for profile in config.profiles.values_mut() {
+            profile.uuids.retain(|fp, _| !selected.contains(fp));
+        }
+        Ok(())
+    })
+    .context("Failed to write config")
 }
 
 fn find(key: &str, keys: &[Key]) -> Option<Key> {
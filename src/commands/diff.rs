@@ -0,0 +1,140 @@
+use super::push::read_dotenv;
+use super::*;
+use crate::{
+    sdk::SDK,
+    utils::{choice::Choice, config::get_config, local_store::LocalStore},
+};
+use envx::kvpair::{mask, select_environment, KVPair};
+use std::{collections::BTreeMap, path::PathBuf};
+
+/// Exit status when the two sides differ
+const EXIT_DRIFT: i32 = 1;
+/// Exit status when the comparison couldn't be made, e.g. the server is unreachable
+const EXIT_ERROR: i32 = 2;
+
+/// Show what differs between a project and a dotenv file or another project
+///
+/// Exits with status 0 when both sides match, 1 when they differ and 2 when the
+/// comparison failed, like diff(1)
+#[derive(Parser)]
+pub struct Args {
+    /// Dotenv file to compare the project with
+    #[clap(default_value = ".env", conflicts_with = "against")]
+    file: PathBuf,
+
+    /// Compare with another project instead of a file
+    #[clap(short, long)]
+    against: Option<String>,
+
+    /// Key to use
+    #[clap(short, long)]
+    key: Option<String>,
+
+    /// Project ID
+    #[clap(short, long)]
+    project_id: Option<String>,
+
+    /// Environment (e.g. dev, staging, prod), defaults to the one linked to this directory
+    #[clap(short, long)]
+    env: Option<String>,
+
+    /// Show values in plain text instead of masking them
+    #[clap(short, long)]
+    reveal: bool,
+}
+
+pub async fn command(args: Args) -> Result<()> {
+    match compare(args).await {
+        Ok(false) => {
+            println!("No differences");
+            Ok(())
+        }
+        Ok(true) => std::process::exit(EXIT_DRIFT),
+        Err(e) => {
+            eprintln!("{:?}", e);
+            std::process::exit(EXIT_ERROR);
+        }
+    }
+}
+
+/// Returns whether there was any difference
+async fn compare(args: Args) -> Result<bool> {
+    let config = get_config()?;
+    let key = config.get_key_or_default(args.key)?;
+    let project_id = Choice::try_project(args.project_id, &key.fingerprint).await?;
+    let environment = Choice::try_environment(args.env, &project_id)?;
+
+    let fetch = |project_id: String| {
+        let fingerprint = key.fingerprint.clone();
+        let online = config.online;
+        async move {
+            if online {
+                SDK::get_variables_pruned(&project_id, &fingerprint).await
+            } else {
+                LocalStore::get_variables_pruned(&project_id)
+            }
+        }
+    };
+
+    let current = select_environment(fetch(project_id.clone()).await?, environment.as_deref());
+
+    // What the project would become, e.g. after `envx push`
+    let (other, label) = match &args.against {
+        Some(other_id) => {
+            let other = fetch(other_id.clone()).await?;
+            (
+                select_environment(other, environment.as_deref()),
+                format!("project {}", other_id),
+            )
+        }
+        None => (read_dotenv(&args.file)?, args.file.display().to_string()),
+    };
+
+    println!("Comparing project {} with {}", project_id, label);
+
+    Ok(print_diff(&to_map(current), &to_map(other), args.reveal))
+}
+
+/// Later keys win, like in a dotenv file
+fn to_map(kvpairs: Vec<KVPair>) -> BTreeMap<String, String> {
+    kvpairs.into_iter().map(|k| (k.key, k.value)).collect()
+}
+
+/// Returns whether there was any difference
+fn print_diff(
+    current: &BTreeMap<String, String>,
+    other: &BTreeMap<String, String>,
+    reveal: bool,
+) -> bool {
+    let show = |value: &str| {
+        if reveal {
+            value.to_string()
+        } else {
+            mask(value)
+        }
+    };
+
+    let mut drift = false;
+    for (key, value) in other.iter() {
+        match current.get(key) {
+            None => println!("{} {} = {}", "+".green(), key.green(), show(value)),
+            Some(old) if old != value => {
+                println!(
+                    "{} {}: {} -> {}",
+                    "~".yellow(),
+                    key.yellow(),
+                    show(old),
+                    show(value)
+                )
+            }
+            Some(_) => continue,
+        }
+        drift = true;
+    }
+    for (key, value) in current.iter().filter(|(k, _)| !other.contains_key(*k)) {
+        println!("{} {} = {}", "-".red(), key.red(), show(value));
+        drift = true;
+    }
+
+    drift
+}
@@ -1,9 +1,7 @@
 use anyhow::Context;
 
-use crate::utils::{
-    config::get_config,
-    rpgp::{encrypt, get_vault_location},
-};
+use crate::utils::{config::get_config, rpgp::get_vault_location};
+use envx::crypto::encrypt;
 
 use super::*;
 
@@ -0,0 +1,113 @@
+use super::*;
+use crate::{
+    sdk::SDK,
+    utils::{choice::Choice, config::get_config, local_store::LocalStore},
+};
+use anyhow::bail;
+use envx::kvpair::KVPair;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// List the environments of a project and the keys missing from each of them
+#[derive(Parser)]
+pub struct Args {
+    /// Key to use
+    #[clap(short, long)]
+    key: Option<String>,
+
+    /// Project ID
+    #[clap(short, long)]
+    project_id: Option<String>,
+
+    /// Only report keys that exist in one environment but are missing from another
+    #[clap(short, long)]
+    missing: bool,
+}
+
+pub async fn command(args: Args) -> Result<()> {
+    let config = get_config()?;
+    let key = config.get_key_or_default(args.key)?;
+    let project_id = Choice::try_project(args.project_id, &key.fingerprint).await?;
+
+    let kvpairs = if config.online {
+        SDK::get_variables_pruned(&project_id, &key.fingerprint).await?
+    } else {
+        LocalStore::get_variables_pruned(&project_id)?
+    };
+
+    let (shared, environments) = group_by_environment(&kvpairs);
+
+    if environments.is_empty() {
+        println!("No environments in project {}", project_id);
+        println!("Use `envx set --env <name> KEY=value` to create one");
+        return Ok(());
+    }
+
+    // Keys without an environment are shared by all of them
+    let all_keys = environments
+        .values()
+        .flatten()
+        .cloned()
+        .collect::<BTreeSet<String>>();
+    let missing = environments
+        .iter()
+        .map(|(env, keys)| {
+            let missing = all_keys
+                .iter()
+                .filter(|k| !keys.contains(*k) && !shared.contains(*k))
+                .cloned()
+                .collect::<Vec<String>>();
+            (env, missing)
+        })
+        .collect::<Vec<_>>();
+
+    if !args.missing {
+        println!("{} shared variable(s)", shared.len());
+        for (env, keys) in environments.iter() {
+            println!("{} - {} variable(s)", env.bold(), keys.len());
+        }
+    }
+
+    let mut drift = false;
+    for (env, keys) in missing.iter().filter(|(_, keys)| !keys.is_empty()) {
+        drift = true;
+        println!(
+            "{} {} is missing {}",
+            "Missing:".yellow(),
+            env.bold(),
+            keys.join(", ")
+        );
+    }
+
+    if args.missing && drift {
+        bail!("Some environments are missing keys");
+    }
+    if args.missing {
+        println!("Every environment has the same keys");
+    }
+
+    Ok(())
+}
+
+/// Keys without an environment, and the keys of every environment
+fn group_by_environment(
+    kvpairs: &[KVPair],
+) -> (BTreeSet<String>, BTreeMap<String, BTreeSet<String>>) {
+    let mut shared = BTreeSet::new();
+    let mut environments = BTreeMap::<String, BTreeSet<String>>::new();
+
+    for kvpair in kvpairs {
+        match &kvpair.environment {
+            Some(env) => {
+                environments
+                    .entry(env.clone())
+                    .or_default()
+                    .insert(kvpair.key.clone());
+            }
+            None => {
+                shared.insert(kvpair.key.clone());
+            }
+        }
+    }
+
+    (shared, environments)
+}
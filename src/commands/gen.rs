@@ -3,16 +3,15 @@
 use super::*;
 use crate::sdk::SDK;
 use crate::utils::config::{self};
-use crate::utils::key::Key;
 use crate::utils::keyring::set_password;
 // use crate::utils::prompt::prompt_password;
 use crate::constants::MINIMUM_PASSWORD_LENGTH;
 use crate::utils::prompt::{prompt_email, prompt_password, prompt_text};
-use crate::utils::rpgp::{generate_hashed_primary_user_id, generate_key_pair, get_vault_location};
+use crate::utils::rpgp::{generate_hashed_primary_user_id, save_key_pair};
 use crate::utils::vecu8::ToHex;
 use anyhow::Context;
+use envx::crypto::{generate_key_pair, KeyAlgorithm};
 use pgp::types::KeyTrait;
-use std::fs;
 use std::str;
 
 extern crate keyring;
@@ -52,6 +51,10 @@ pub struct Args {
 
     #[clap(long)]
     export: bool,
+
+    /// Key algorithm, ed25519 is the fastest
+    #[clap(short, long, value_enum, default_value_t)]
+    algorithm: KeyAlgorithm,
 }
 
 fn email_validator(email: &str) -> anyhow::Result<(), anyhow::Error> {
@@ -71,7 +74,7 @@ fn email_validator(email: &str) -> anyhow::Result<(), anyhow::Error> {
 }
 
 pub async fn command(args: Args) -> Result<()> {
-    let mut config = config::get_config().context("Failed to get config")?;
+    let config = config::get_config().context("Failed to get config")?;
     let settings = config.get_settings()?;
 
     let name = args
@@ -102,8 +105,12 @@ pub async fn command(args: Args) -> Result<()> {
         eprintln!("You can disable this warning with `envx config --no-warn-on-short-passwords`");
     }
 
-    let key_pair = generate_key_pair(name.clone(), email.clone(), passphrase.to_owned())
-        .expect("Failed to generate key pair");
+    let key_pair = generate_key_pair(
+        generate_hashed_primary_user_id(name.clone(), email.clone()),
+        passphrase.to_owned(),
+        args.algorithm,
+    )
+    .expect("Failed to generate key pair");
 
     let priv_key = key_pair
         .secret_key
@@ -154,28 +161,14 @@ pub async fn command(args: Args) -> Result<()> {
         return Ok(());
     }
 
-    let key_dir = get_vault_location()?.join(fingerprint.clone());
-
-    fs::create_dir_all(&key_dir).context("Failed to create key directory")?;
-
-    fs::write(key_dir.join("private.key"), &priv_key).expect("Failed to write private key to file");
-    fs::write(key_dir.join("public.key"), &pub_key).expect("Failed to write public key to file");
-
-    let hashed_note = generate_hashed_primary_user_id(name.clone(), email.clone());
-    let mut key_to_insert: Key = Key {
-        fingerprint: fingerprint.clone(),
-        note: "".to_string(),
-        primary_user_id: format!("{} <{}>", &name, &email),
-        hashed_note: hashed_note.clone(),
-        pubkey_only: None,
-        uuid: None,
-    };
+    let key = save_key_pair(&key_pair, &name, &email)?;
 
+    let mut uuid = None;
     if config.online {
         match SDK::new_user(&username, &pub_key).await {
             Ok(id) => {
                 println!("User ID: {}", id);
-                key_to_insert.uuid = Some(id);
+                uuid = Some(id);
             }
             Err(_) => {
                 eprintln!("Failed to create user on API");
@@ -185,14 +178,17 @@ pub async fn command(args: Args) -> Result<()> {
         };
     }
 
-    config.keys.push(key_to_insert);
-
-    if config.primary_key.is_empty() {
-        println!("Setting primary key to {}...", &fingerprint);
-        config.primary_key = fingerprint;
-    }
-
-    config.write().context("Failed to write config")?;
+    config::Config::update(|config| {
+        config.keys.push(key);
+        if let Some(uuid) = &uuid {
+            config.set_key_uuid(&fingerprint, uuid)?;
+        }
 
-    Ok(())
+        if config.primary_key.is_empty() {
+            println!("Setting primary key to {}...", &fingerprint);
+            config.primary_key = fingerprint.clone();
+        }
+        Ok(())
+    })
+    .context("Failed to write config")
 }
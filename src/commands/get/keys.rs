@@ -18,10 +18,9 @@ pub async fn command(_args: Args) -> Result<()> {
             false => &key.fingerprint[..8],
         };
 
-        let uuid = match &key.uuid {
-            Some(uuid) => uuid,
-            None => "Not on remote",
-        };
+        let uuid = config
+            .key_uuid(key)?
+            .unwrap_or_else(|| "Not on remote".into());
 
         println!("\t{} {} | {}", fingerprint, key.primary_user_id, uuid);
     }
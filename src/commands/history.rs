@@ -0,0 +1,163 @@
+use super::*;
+use crate::{
+    sdk::SDK,
+    utils::{choice::Choice, config::get_config, local_store::LocalStore},
+};
+use anyhow::bail;
+use envx::{
+    kvpair::mask,
+    partial_variable::{newest_first, ParsedPartialVariable, ToParsed},
+};
+
+/// Show every stored version of a variable
+#[derive(Parser)]
+pub struct Args {
+    /// Name of the variable
+    variable: String,
+
+    /// Key to use
+    #[clap(short, long)]
+    key: Option<String>,
+
+    /// Project ID
+    #[clap(short, long)]
+    project_id: Option<String>,
+
+    /// Show values in plain text instead of masking them
+    #[clap(short, long)]
+    reveal: bool,
+
+    /// Environment of the variable, defaults to the one linked to this directory
+    #[clap(short, long)]
+    env: Option<String>,
+}
+
+pub async fn command(args: Args) -> Result<()> {
+    let config = get_config()?;
+    let key = config.get_key_or_default(args.key)?;
+    let project_id = Choice::try_project(args.project_id, &key.fingerprint).await?;
+
+    let environment = Choice::try_environment(args.env, &project_id)?;
+
+    let name = args.variable.to_uppercase();
+    let versions = get_versions(
+        &name,
+        environment.as_deref(),
+        &project_id,
+        &key.fingerprint,
+        config.online,
+    )
+    .await?;
+
+    let label = match &environment {
+        Some(env) => format!("{} [{}]", name, env),
+        None => name,
+    };
+
+    if versions.is_empty() {
+        bail!("No versions of {} found in project {}", label, project_id);
+    }
+
+    println!("{} - {} version(s)", label.bold(), versions.len());
+
+    for (i, version) in versions.iter().enumerate() {
+        let timestamp = match version.created_at() {
+            Ok(t) => t.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+            Err(_) => version.created_at.clone(),
+        };
+        let current = if i == 0 {
+            " (current)".green()
+        } else {
+            "".normal()
+        };
+
+        println!();
+        println!("{} {}{}", timestamp.cyan(), version.id.dimmed(), current);
+
+        let value = &version.value.value;
+        match versions.get(i + 1) {
+            None => print_value("+", value, args.reveal),
+            Some(previous) if previous.value.value == *value => {
+                println!("  {}", "unchanged".dimmed());
+            }
+            Some(previous) if !args.reveal => {
+                println!(
+                    "  {} {} -> {}",
+                    "changed".yellow(),
+                    mask(&previous.value.value),
+                    mask(value)
+                );
+            }
+            Some(previous) => print_diff(&previous.value.value, value),
+        }
+    }
+
+    Ok(())
+}
+
+/// Every version of a variable in an environment, newest first
+pub(super) async fn get_versions(
+    name: &str,
+    environment: Option<&str>,
+    project_id: &str,
+    fingerprint: &str,
+    online: bool,
+) -> Result<Vec<ParsedPartialVariable>> {
+    let (kvpairs, partials) = if online {
+        SDK::get_variables(project_id, fingerprint).await?
+    } else {
+        LocalStore::get_variables(project_id)?
+    };
+
+    let mut versions = partials
+        .zip_to_parsed(kvpairs)
+        .into_iter()
+        .filter(|p| p.value.key == name && p.value.environment.as_deref() == environment)
+        .collect::<Vec<ParsedPartialVariable>>();
+    versions.sort_by(newest_first);
+
+    Ok(versions)
+}
+
+fn print_value(prefix: &str, value: &str, reveal: bool) {
+    if !reveal {
+        println!("  {} {}", prefix.green(), mask(value));
+        return;
+    }
+    for line in value.lines() {
+        println!("  {} {}", prefix.green(), line.green());
+    }
+}
+
+/// Line diff between two values, based on their longest common subsequence
+fn print_diff(old: &str, new: &str) {
+    let old = old.lines().collect::<Vec<&str>>();
+    let new = new.lines().collect::<Vec<&str>>();
+
+    // lcs[i][j] = length of the LCS of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            println!("    {}", old[i].dimmed());
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            println!("  {} {}", "+".green(), new[j].green());
+            j += 1;
+        } else {
+            println!("  {} {}", "-".red(), old[i].red());
+            i += 1;
+        }
+    }
+}
@@ -1,12 +1,18 @@
 use super::*;
-use crate::utils::key::Key;
-use crate::utils::prompt::prompt_text;
-use crate::utils::rpgp::get_vault_location;
+use crate::utils::config::{get_config, Config};
+use crate::utils::gnupg;
+use crate::utils::keyring::set_password;
+use crate::utils::prompt::{prompt_email, prompt_password, prompt_text};
+use crate::utils::rpgp::import_key;
 use crate::utils::vecu8::ToHex;
+use anyhow::bail;
 use clap::Subcommand;
-use pgp::{types::KeyTrait, Deserializable};
-use std::fs;
-use std::io::Cursor;
+use envx::crypto::{ensure_usable, signed_public_key};
+use pgp::{
+    types::{KeyTrait, SecretKeyTrait},
+    Deserializable, SignedPublicKey, SignedSecretKey,
+};
+use std::{fs, path::PathBuf};
 
 /// Import ascii armored keys from a file
 #[derive(Parser, Debug)]
@@ -17,61 +23,273 @@ pub struct Args {
 
 #[derive(Subcommand, Debug)]
 pub enum Commands {
-    /// Import a public key
+    /// Import public keys, e.g. of the users to add to a project
     Pubkey { path: String },
+    /// Import secret keys, e.g. when moving to a new machine
+    Secret {
+        path: String,
+
+        /// Check the passphrase of each key and store it in the keyring
+        #[clap(long)]
+        save_passphrase: bool,
+
+        /// ID of the user the key was uploaded as, only when importing a single key
+        #[clap(short, long)]
+        user_id: Option<String>,
+    },
+    /// Import your own keys from GnuPG, gpg may ask for their passphrases
+    Gpg {
+        /// GnuPG home directory, defaults to $GNUPGHOME or ~/.gnupg
+        #[clap(long)]
+        homedir: Option<PathBuf>,
+
+        /// Only import this key, by fingerprint, key ID or part of its user ID
+        #[clap(short, long)]
+        key: Option<String>,
+    },
+}
+
+/// A checked key, ready to be written to the vault
+struct Import {
+    pubkey: SignedPublicKey,
+    seckey: Option<SignedSecretKey>,
+    /// Passphrase to store in the keyring
+    passphrase: Option<String>,
+    /// ID of the user the key was uploaded as
+    user_id: Option<String>,
+    /// (primary_user_id, hashed_note) of a key that isn't in the config yet
+    owner: Option<(String, String)>,
+}
+
+impl Import {
+    fn new(pubkey: SignedPublicKey, seckey: Option<SignedSecretKey>) -> Self {
+        Self {
+            pubkey,
+            seckey,
+            passphrase: None,
+            user_id: None,
+            owner: None,
+        }
+    }
 }
 
 pub async fn command(args: Args) -> Result<()> {
-    let mut vault_path = get_vault_location()?;
-    match args.command {
+    // Everything that prompts or runs gpg happens before the config is locked, so other
+    // envx commands don't wait for the user
+    let mut imports = match args.command {
         Commands::Pubkey { path } => {
-            let buf = Cursor::new(fs::read_to_string(path).context("Failed to read file")?);
-            let (pubkey, _) = pgp::composed::SignedPublicKey::from_armor_single(buf)
-                .context("Failed to parse armored key")?;
+            let pubkeys = parse_keys::<SignedPublicKey>(&path, "PUBLIC KEY BLOCK")?;
+            for pubkey in pubkeys.iter() {
+                pubkey.verify().with_context(|| {
+                    format!(
+                        "Invalid self-signature on key {}",
+                        pubkey.fingerprint().to_hex()
+                    )
+                })?;
+            }
 
-            let fingerprint = pubkey.fingerprint().to_hex().to_uppercase();
+            pubkeys
+                .into_iter()
+                .map(|pubkey| Import::new(pubkey, None))
+                .collect::<Vec<Import>>()
+        }
+        Commands::Secret {
+            path,
+            save_passphrase,
+            user_id,
+        } => {
+            let seckeys = parse_keys::<SignedSecretKey>(&path, "PRIVATE KEY BLOCK")?;
+            if user_id.is_some() && seckeys.len() > 1 {
+                bail!("--user-id can only be used when the file holds a single key");
+            }
 
-            println!("Importing key: {}", fingerprint);
+            // Every key is checked before anything is written, so a wrong passphrase
+            // doesn't leave half an import in the vault
+            let mut imports = vec![];
+            for seckey in seckeys {
+                let fingerprint = seckey.fingerprint().to_hex();
+                seckey
+                    .verify()
+                    .with_context(|| format!("Invalid self-signature on key {}", fingerprint))?;
 
-            let first_user_id = pubkey
-                .details
-                .users
-                .first()
-                .context("Failed to get user id from key")?
-                .id
-                .id()
-                .to_string();
-
-            let (primary_user_id, hashed_note) =
-                if only_hex(&first_user_id) && first_user_id.len() == 128 {
-                    println!("This key has no user id because it was generated by env-cli.");
-                    println!("Please enter the name and email of the owner of this key.");
-                    println!("User Id: {}", first_user_id);
-
-                    let name = prompt_text("What is the name of the owner of this key?")?;
-                    let email = prompt_text("What is the email of the owner of this key?")?;
-                    (format!("{} <{}>", name, email), first_user_id)
+                let passphrase = if save_passphrase {
+                    let passphrase =
+                        prompt_password(&format!("Passphrase of key {}", fingerprint))?;
+                    seckey
+                        .unlock(|| passphrase.clone(), |_| Ok(()))
+                        .with_context(|| format!("Wrong passphrase for key {}", fingerprint))?;
+                    Some(passphrase)
                 } else {
-                    (first_user_id, "".to_string())
+                    None
                 };
 
-            let key = Key {
-                fingerprint,
-                note: "".to_string(),
-                pubkey_only: Some(true),
-                primary_user_id,
-                hashed_note,
-                uuid: None,
-            };
-
-            vault_path.push(format!("{}/public.key", &key.fingerprint));
-            fs::write(vault_path, pubkey.to_armored_string(None)?)?;
+                imports.push(Import {
+                    passphrase,
+                    user_id: user_id.clone(),
+                    ..Import::new(signed_public_key(&seckey), Some(seckey))
+                });
+            }
+            imports
+        }
+        Commands::Gpg { homedir, key } => {
+            let home = gnupg::home_dir(homedir)?;
+
+            let pubkeys = gnupg::public_keys(&home)?
+                .into_iter()
+                .filter(|k| key.as_ref().map_or(true, |key| matches_key(k, key)))
+                .collect::<Vec<SignedPublicKey>>();
+            if pubkeys.is_empty() {
+                bail!("No matching keys in {}", home.display());
+            }
+
+            let mut imports = vec![];
+            for pubkey in pubkeys {
+                let fingerprint = pubkey.fingerprint().to_hex();
+                if let Err(e) = pubkey.verify().context("Invalid self-signature") {
+                    eprintln!("Skipping {}: {:#}", fingerprint, e);
+                    continue;
+                }
+                if let Err(e) = ensure_usable(&pubkey) {
+                    eprintln!("Skipping: {:#}", e);
+                    continue;
+                }
+                let seckey = match gnupg::secret_key(&home, &fingerprint) {
+                    Ok(Some(seckey)) => seckey,
+                    Ok(None) => {
+                        eprintln!("Skipping {}: no secret key", fingerprint);
+                        continue;
+                    }
+                    Err(e) => {
+                        eprintln!("Skipping {}: {:#}", fingerprint, e);
+                        continue;
+                    }
+                };
+                if let Err(e) = seckey
+                    .verify()
+                    .context("Invalid self-signature on the secret key")
+                {
+                    eprintln!("Skipping {}: {:#}", fingerprint, e);
+                    continue;
+                }
+
+                imports.push(Import::new(pubkey, Some(seckey)));
+            }
+            imports
+        }
+    };
+
+    let known = get_config()?
+        .keys
+        .into_iter()
+        .map(|k| k.fingerprint)
+        .collect::<Vec<String>>();
+    for import in imports.iter_mut() {
+        if !known.contains(&import.pubkey.fingerprint().to_hex()) {
+            import.owner = Some(owner(&import.pubkey)?);
+        }
+    }
+
+    Config::update(|config| {
+        for import in imports.iter() {
+            let fingerprint = import.pubkey.fingerprint().to_hex();
+            println!("Importing key: {}", fingerprint);
+            // The owner is only missing if another envx process added the key meanwhile
+            import_key(
+                config,
+                &import.pubkey,
+                import.seckey.as_ref(),
+                || match &import.owner {
+                    Some(owner) => Ok(owner.clone()),
+                    None => owner(&import.pubkey),
+                },
+            )?;
+
+            if let Some(user_id) = &import.user_id {
+                config.set_key_uuid(&fingerprint, user_id)?;
+            }
+            if import.seckey.is_some() && config.primary_key.is_empty() {
+                println!("Setting primary key to {}...", &fingerprint);
+                config.primary_key = fingerprint;
+            }
+        }
+        Ok(())
+    })
+    .context("Failed to write config")?;
+
+    for import in imports.iter() {
+        if let Some(passphrase) = &import.passphrase {
+            if let Err(e) = set_password(&import.pubkey.fingerprint().to_hex(), passphrase) {
+                eprintln!("Failed to save the passphrase in the keyring: {}", e);
+            }
         }
     }
 
     Ok(())
 }
 
+/// Every key in the armored blocks of a file, a file may hold several blocks
+/// (e.g. concatenated exports) and each block several keys (a keyring)
+fn parse_keys<T: Deserializable>(path: &str, block: &str) -> Result<Vec<T>> {
+    let contents = fs::read_to_string(path).context("Failed to read file")?;
+    let begin = format!("-----BEGIN PGP {}-----", block);
+
+    let mut keys = vec![];
+    for (start, _) in contents.match_indices(&begin) {
+        let (parsed, _) =
+            T::from_string_many(&contents[start..]).context("Failed to parse armored key")?;
+        for key in parsed {
+            keys.push(key.context("Failed to parse armored key")?);
+        }
+    }
+
+    if keys.is_empty() {
+        bail!("No {} found in {}", block.to_lowercase(), path);
+    }
+    Ok(keys)
+}
+
+/// The user ID of a key, and its hashed note
+///
+/// Keys generated by env-cli only carry a hash of the name and email of their owner, so
+/// those are asked for
+fn owner(pubkey: &SignedPublicKey) -> Result<(String, String)> {
+    let first_user_id = pubkey
+        .details
+        .users
+        .first()
+        .context("Failed to get user id from key")?
+        .id
+        .id()
+        .to_string();
+
+    if !(only_hex(&first_user_id) && first_user_id.len() == 128) {
+        return Ok((first_user_id, "".to_string()));
+    }
+
+    println!("This key has no user id because it was generated by env-cli.");
+    println!("Please enter the name and email of the owner of this key.");
+    println!("User Id: {}", first_user_id);
+
+    let name = prompt_text("What is the name of the owner of this key?")?;
+    let email = prompt_email("What is the email of the owner of this key?")?;
+    Ok((format!("{} <{}>", name, email), first_user_id))
+}
+
+/// Whether a GnuPG key selector (fingerprint, key ID or part of a user ID) matches a key
+fn matches_key(pubkey: &SignedPublicKey, selector: &str) -> bool {
+    let id = selector.trim_start_matches("0x").to_lowercase();
+    if only_hex(&id) && id.len() >= 8 && pubkey.fingerprint().to_hex().ends_with(&id) {
+        return true;
+    }
+
+    pubkey.details.users.iter().any(|u| {
+        u.id.id()
+            .to_string()
+            .to_lowercase()
+            .contains(&selector.to_lowercase())
+    })
+}
+
 fn only_hex(s: &str) -> bool {
     s.chars().all(|c| c.is_ascii_hexdigit())
 }
@@ -0,0 +1,130 @@
+use super::*;
+use crate::utils::{
+    config::Config,
+    rpgp::import_key,
+    shamir::{self, Share},
+    vecu8::ToHex,
+};
+use anyhow::bail;
+use envx::crypto::signed_public_key;
+use pgp::{types::KeyTrait, Deserializable, SignedSecretKey};
+use std::{
+    fs,
+    io::{Cursor, Read},
+    path::PathBuf,
+};
+
+/// Rebuild a secret key from the shares of `envx key split` and add it to the vault
+#[derive(Parser)]
+pub struct Args {
+    /// Files holding the shares, read from stdin if none are given
+    paths: Vec<PathBuf>,
+
+    /// ID of the user the key was uploaded as
+    #[clap(short, long)]
+    user_id: Option<String>,
+}
+
+pub async fn command(args: Args) -> Result<()> {
+    let mut text = String::new();
+    if args.paths.is_empty() {
+        eprintln!("Paste the shares, then press Ctrl-D");
+        std::io::stdin()
+            .read_to_string(&mut text)
+            .context("Failed to read the shares")?;
+    }
+    for path in &args.paths {
+        text.push_str(
+            &fs::read_to_string(path)
+                .with_context(|| format!("Failed to read {}", path.display()))?,
+        );
+        text.push('\n');
+    }
+
+    let mut shares = Share::parse_all(&text)?;
+    let Some(first) = shares.first().cloned() else {
+        bail!("No shares found");
+    };
+    if let Some(other) = shares.iter().find(|s| !s.same_split(&first)) {
+        bail!(
+            "Share {} doesn't belong with share {}, they were made from {}",
+            other.index,
+            first.index,
+            if other.fingerprint == first.fingerprint {
+                "different splits of the key".to_string()
+            } else {
+                format!("keys {} and {}", first.fingerprint, other.fingerprint)
+            }
+        );
+    }
+    // The same share may be in several files
+    shares.sort_by_key(|s| s.index);
+    shares.dedup_by_key(|s| s.index);
+    if shares.len() < first.threshold as usize {
+        bail!(
+            "{} shares are needed to rebuild {}, only got {}",
+            first.threshold,
+            first.fingerprint,
+            shares.len()
+        );
+    }
+
+    let bytes = shamir::combine(
+        &shares
+            .into_iter()
+            .map(|s| (s.index, s.data))
+            .collect::<Vec<(u8, Vec<u8>)>>(),
+    )?;
+    let secret_key = SignedSecretKey::from_bytes(Cursor::new(bytes))
+        .context("The shares didn't rebuild a valid key")?;
+    let fingerprint = secret_key.fingerprint().to_hex();
+    if fingerprint != first.fingerprint {
+        bail!(
+            "The shares rebuilt {} instead of {}",
+            fingerprint,
+            first.fingerprint
+        );
+    }
+    secret_key
+        .verify()
+        .context("Invalid self-signature on the rebuilt key")?;
+
+    Config::update(|config| {
+        if config
+            .keys
+            .iter()
+            .any(|k| k.fingerprint == fingerprint && !k.is_pubkey_only())
+        {
+            bail!("{} is already in the vault", fingerprint);
+        }
+
+        import_key(
+            config,
+            &signed_public_key(&secret_key),
+            Some(&secret_key),
+            || {
+                let hashed_note = secret_key
+                    .details
+                    .users
+                    .first()
+                    .map(|u| u.id.id().to_string())
+                    .filter(|id| id.len() == 128 && id.chars().all(|c| c.is_ascii_hexdigit()))
+                    .unwrap_or_default();
+                Ok((first.user_id.clone(), hashed_note))
+            },
+        )?;
+        if let Some(user_id) = &args.user_id {
+            config.set_key_uuid(&fingerprint, user_id)?;
+        }
+        if config.primary_key.is_empty() {
+            println!("Setting primary key to {}...", &fingerprint);
+            config.primary_key = fingerprint.clone();
+        }
+        Ok(())
+    })
+    .context("Failed to write config")?;
+
+    println!("{} {} ({})", "Rebuilt".green(), fingerprint, first.user_id);
+
+    Ok(())
+}
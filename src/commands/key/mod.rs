@@ -0,0 +1,26 @@
+#[allow(unused_imports)]
+pub(super) use anyhow::{anyhow, Context, Result};
+pub(super) use clap::Parser;
+#[allow(unused_imports)]
+pub(super) use colored::Colorize;
+
+use crate::commands_enum;
+use clap::Subcommand;
+
+pub mod combine;
+pub mod rotate;
+pub mod split;
+
+/// Manage keys. (rotate, split, combine)
+#[derive(Parser)]
+pub struct Args {
+    #[clap(subcommand)]
+    command: Commands,
+}
+
+commands_enum!(rotate, split, combine);
+
+pub async fn command(args: Args) -> Result<()> {
+    Commands::exec(args).await?;
+    Ok(())
+}
@@ -0,0 +1,390 @@
+use super::*;
+use crate::{
+    sdk::SDK,
+    utils::{
+        config::{get_config, Config},
+        keyring::set_password,
+        prompt::{prompt_email, prompt_password, prompt_text},
+        resolve::{active_profile, config_dir},
+        rpgp::{generate_hashed_primary_user_id, save_key_pair},
+    },
+};
+use anyhow::bail;
+use envx::{
+    crypto::{generate_key_pair, KeyAlgorithm},
+    error::Error,
+    file::write_atomic,
+    types::ProjectInfo,
+};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf};
+
+/// Replace a key with a new one on every project it can access
+///
+/// Progress is saved after every step, run the command again to resume after a failure
+#[derive(Parser)]
+pub struct Args {
+    /// Partial fingerprint of the key to replace
+    #[clap(short, long)]
+    key: Option<String>,
+
+    /// Username of the new key on the server
+    #[clap(short, long)]
+    username: Option<String>,
+
+    /// Passphrase of the new key
+    #[clap(long)]
+    passphrase: Option<String>,
+
+    /// Algorithm of the new key
+    #[clap(short, long, value_enum, default_value_t)]
+    algorithm: KeyAlgorithm,
+
+    /// Remove the old key from every project and delete its user, e.g. if it was lost
+    #[clap(long)]
+    delete_old: bool,
+
+    /// Forget an unfinished rotation, the new key is kept
+    #[clap(long)]
+    abort: bool,
+}
+
+/// Progress of a rotation, in rotation.json in the config directory
+#[derive(Serialize, Deserialize)]
+struct Rotation {
+    old_fingerprint: String,
+    new_fingerprint: String,
+    /// Username the new key is registered as
+    username: String,
+    profile: String,
+    delete_old: bool,
+    /// Projects of the old key, `None` until they are listed
+    pending: Option<Vec<String>>,
+    /// The old user was deleted from the server
+    #[serde(default)]
+    deleted_old: bool,
+}
+
+impl Rotation {
+    fn path() -> Result<PathBuf> {
+        Ok(config_dir()?.value.join("rotation.json"))
+    }
+
+    fn load() -> Result<Option<Self>> {
+        let path = Self::path()?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        let contents = fs::read_to_string(&path).context("Failed to read rotation state")?;
+        Ok(Some(
+            serde_json::from_str(&contents).context("Failed to parse rotation state")?,
+        ))
+    }
+
+    fn save(&self) -> Result<()> {
+        Ok(write_atomic(
+            &Self::path()?,
+            &serde_json::to_string_pretty(self)?,
+        )?)
+    }
+
+    fn remove() -> Result<()> {
+        fs::remove_file(Self::path()?).context("Failed to remove rotation state")
+    }
+}
+
+pub async fn command(args: Args) -> Result<()> {
+    let config = get_config()?;
+    if !config.online {
+        bail!("Key rotation needs the server, run `envx config set online true`");
+    }
+
+    if args.abort {
+        let Some(rotation) = Rotation::load()? else {
+            bail!("No rotation in progress");
+        };
+        Rotation::remove()?;
+        println!(
+            "Forgot the rotation of {}, the new key {} is kept",
+            rotation.old_fingerprint, rotation.new_fingerprint
+        );
+        return Ok(());
+    }
+
+    let mut rotation = match Rotation::load()? {
+        Some(rotation) => {
+            if let Some(key) = &args.key {
+                if config.get_key(key)?.fingerprint != rotation.old_fingerprint {
+                    bail!(
+                        "The rotation of {} isn't finished, resume it first or drop it with --abort",
+                        rotation.old_fingerprint
+                    );
+                }
+            }
+            if rotation.profile != active_profile() {
+                bail!(
+                    "The rotation of {} was started on profile {}, resume it with --profile {}",
+                    rotation.old_fingerprint,
+                    rotation.profile,
+                    rotation.profile
+                );
+            }
+            println!(
+                "Resuming the rotation of {} to {}",
+                rotation.old_fingerprint, rotation.new_fingerprint
+            );
+            rotation
+        }
+        None => start(&args, config)?,
+    };
+
+    let config = get_config()?;
+    let old = config.get_key(&rotation.old_fingerprint)?;
+    let new = config.get_key(&rotation.new_fingerprint)?;
+    let old_uuid = config
+        .key_uuid(&old)?
+        .context("The old key isn't on the server, there is nothing to rotate")?;
+
+    let new_uuid = match config.key_uuid(&new)? {
+        Some(uuid) => uuid,
+        None => {
+            let uuid = SDK::new_user(&rotation.username, &new.public_key()?).await?;
+            Config::update(|config| config.set_key_uuid(&new.fingerprint, &uuid))?;
+            println!("Registered the new key as {}", uuid);
+            uuid
+        }
+    };
+
+    let pending = match rotation.pending.take() {
+        Some(pending) => pending,
+        None => SDK::list_projects(&old.fingerprint).await?,
+    };
+    rotation.pending = Some(pending.clone());
+    rotation.save()?;
+
+    for project_id in pending {
+        migrate_project(
+            &project_id,
+            &old.fingerprint,
+            &old_uuid,
+            &new.fingerprint,
+            &new_uuid,
+            rotation.delete_old,
+        )
+        .await
+        .with_context(|| {
+            format!(
+                "Failed to move project {}, run `envx key rotate` again to resume",
+                project_id
+            )
+        })?;
+
+        if let Some(pending) = rotation.pending.as_mut() {
+            pending.retain(|p| *p != project_id);
+        }
+        rotation.save()?;
+        println!("{} {}", "Moved project".green(), project_id);
+    }
+
+    if rotation.delete_old && !rotation.deleted_old {
+        SDK::delete_key(&old.fingerprint).await?;
+        rotation.deleted_old = true;
+        rotation.save()?;
+        println!("Deleted the old user {}", old_uuid);
+    }
+
+    Config::update(|config| {
+        if rotation.delete_old {
+            config.unset_key_uuid(&old.fingerprint)?;
+        }
+        if config.primary_key == old.fingerprint {
+            config.primary_key = new.fingerprint.clone();
+        }
+        for profile in config.profiles.values_mut() {
+            if profile.default_key.as_deref() == Some(old.fingerprint.as_str()) {
+                profile.default_key = Some(new.fingerprint.clone());
+            }
+        }
+        Ok(())
+    })?;
+    Rotation::remove()?;
+
+    println!("{} {}", "Rotated to".green(), new);
+    println!(
+        "The old key is still on disk, remove it with `envx delete key -k {}`",
+        old.fingerprint
+    );
+
+    Ok(())
+}
+
+/// Generate the new key and save the rotation before anything touches the server
+fn start(args: &Args, config: Config) -> Result<Rotation> {
+    let old = config.get_key_or_default(args.key.clone())?;
+
+    // "Name <email>"
+    let (name, email) = match old.primary_user_id.rsplit_once(" <") {
+        Some((name, email)) => (name.to_string(), email.trim_end_matches('>').to_string()),
+        None => (prompt_text("What is your name?")?, prompt_email("email")?),
+    };
+    let username = match &args.username {
+        Some(u) => u.clone(),
+        None => prompt_text("Username for the new key:")?,
+    };
+    let passphrase = match &args.passphrase {
+        Some(p) => p.clone(),
+        None => prompt_password("Passphrase for the new key")?,
+    };
+
+    println!("Generating a new key for {} <{}>...", name, email);
+    let key_pair = generate_key_pair(
+        generate_hashed_primary_user_id(name.clone(), email.clone()),
+        passphrase.clone(),
+        args.algorithm,
+    )?;
+    let new = save_key_pair(&key_pair, &name, &email)?;
+    if let Err(e) = set_password(&new.fingerprint, &passphrase) {
+        eprintln!("Failed to save the passphrase in the keyring: {}", e);
+    }
+
+    let rotation = Rotation {
+        old_fingerprint: old.fingerprint,
+        new_fingerprint: new.fingerprint.clone(),
+        username,
+        profile: active_profile(),
+        delete_old: args.delete_old,
+        pending: None,
+        deleted_old: false,
+    };
+
+    Config::update(|config| {
+        config.keys.push(new);
+        Ok(())
+    })?;
+    rotation.save()?;
+
+    Ok(rotation)
+}
+
+/// Give the new key access to a project, and take it from the old one if asked to
+///
+/// Every step can be repeated, the project is in a usable state between them
+async fn migrate_project(
+    project_id: &str,
+    old_fingerprint: &str,
+    old_uuid: &str,
+    new_fingerprint: &str,
+    new_uuid: &str,
+    delete_old: bool,
+) -> Result<()> {
+    // Once the new key is a member it's used for everything, the old one may already be gone
+    let info = match SDK::get_project_info(project_id, new_fingerprint).await {
+        Ok(info) => info,
+        Err(e) if is_not_found(&e) => {
+            SDK::add_user_to_project(old_fingerprint, new_uuid, project_id).await?;
+            SDK::get_project_info(project_id, new_fingerprint).await?
+        }
+        Err(e) => return Err(e),
+    };
+
+    SDK::reencrypt_variables(
+        project_id,
+        new_fingerprint,
+        &recipients(&info, old_uuid, delete_old),
+    )
+    .await?;
+
+    if delete_old && info.users.iter().any(|u| u.id == old_uuid) {
+        SDK::remove_user_from_project(new_fingerprint, old_uuid, project_id).await?;
+    }
+
+    Ok(())
+}
+
+/// Public keys a project is re-encrypted to, without the old key if it's being removed
+fn recipients(info: &ProjectInfo, old_uuid: &str, delete_old: bool) -> Vec<String> {
+    info.users
+        .iter()
+        .filter(|u| !(delete_old && u.id == old_uuid))
+        .map(|u| u.public_key.clone())
+        .collect()
+}
+
+/// The server answers 404 to keys that aren't members of a project
+fn is_not_found(e: &anyhow::Error) -> bool {
+    matches!(e.downcast_ref::<Error>(), Some(Error::NotFound(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use envx::types::User;
+
+    fn user(id: &str) -> User {
+        User {
+            id: id.to_string(),
+            username: id.to_string(),
+            created_at: "".to_string(),
+            public_key: format!("{} key", id),
+        }
+    }
+
+    #[test]
+    fn only_a_missing_project_adds_the_new_key() {
+        let not_found = anyhow::Error::from(Error::NotFound("Project not found".to_string()))
+            .context("Failed to get project info");
+        assert!(is_not_found(&not_found));
+
+        // Anything else must not be taken for "not a member yet"
+        for e in [
+            Error::Network("Request timed out".to_string()),
+            Error::Unauthorized("Invalid token".to_string()),
+        ] {
+            assert!(!is_not_found(
+                &anyhow::Error::from(e).context("Failed to get project info")
+            ));
+        }
+        assert!(!is_not_found(&anyhow::anyhow!("Not found")));
+    }
+
+    #[test]
+    fn drops_the_old_key_from_the_recipients_when_deleting_it() {
+        let info = ProjectInfo {
+            project_id: "project".to_string(),
+            users: vec![user("old"), user("new"), user("other")],
+        };
+
+        assert_eq!(
+            recipients(&info, "old", false),
+            ["old key", "new key", "other key"]
+        );
+        assert_eq!(recipients(&info, "old", true), ["new key", "other key"]);
+    }
+
+    #[test]
+    fn resumes_a_rotation_saved_before_deleted_old_existed() {
+        let rotation = serde_json::from_str::<Rotation>(
+            r#"{
+                "old_fingerprint": "old",
+                "new_fingerprint": "new",
+                "username": "me",
+                "profile": "default",
+                "delete_old": true,
+                "pending": []
+            }"#,
+        )
+        .unwrap();
+        assert!(!rotation.deleted_old);
+
+        let rotation = Rotation {
+            deleted_old: true,
+            ..rotation
+        };
+        let saved = serde_json::to_string(&rotation).unwrap();
+        assert!(
+            serde_json::from_str::<Rotation>(&saved)
+                .unwrap()
+                .deleted_old
+        );
+    }
+}
@@ -0,0 +1,84 @@
+use super::*;
+use crate::utils::{config::get_config, shamir, vecu8::ToHex};
+use envx::file::write_private;
+use pgp::{ser::Serialize, types::KeyTrait, Deserializable, SignedSecretKey};
+use std::{fs, path::PathBuf};
+
+/// Split a secret key into shares, any `threshold` of which rebuild it with `envx key combine`
+///
+/// The key stays protected by its passphrase, and stays in the vault until you delete it
+#[derive(Parser)]
+pub struct Args {
+    /// Partial fingerprint of the key to split
+    #[clap(short, long)]
+    key: Option<String>,
+
+    /// Number of shares to make
+    #[clap(short, long, default_value_t = 5)]
+    shares: u8,
+
+    /// Number of shares needed to rebuild the key
+    #[clap(short, long, default_value_t = 3)]
+    threshold: u8,
+
+    /// Write each share to its own file in this directory instead of printing them
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+}
+
+pub async fn command(args: Args) -> Result<()> {
+    let config = get_config()?;
+    let key = config.get_key_or_default(args.key)?;
+    if key.is_pubkey_only() {
+        return Err(anyhow!("Only the public key of {} is in the vault", key));
+    }
+
+    let (secret_key, _) = SignedSecretKey::from_string(&key.secret_key()?)
+        .context("Failed to parse the secret key")?;
+    let bytes = secret_key
+        .to_bytes()
+        .context("Failed to serialize the secret key")?;
+
+    let fingerprint = secret_key.fingerprint().to_hex();
+    let split_id = hex::encode(rand::random::<[u8; 4]>());
+    let shares = shamir::split(&bytes, args.shares, args.threshold)?
+        .into_iter()
+        .map(|(index, data)| shamir::Share {
+            fingerprint: fingerprint.clone(),
+            split_id: split_id.clone(),
+            index,
+            shares: args.shares,
+            threshold: args.threshold,
+            user_id: key.primary_user_id.clone(),
+            data,
+        })
+        .collect::<Vec<shamir::Share>>();
+
+    match &args.output {
+        Some(dir) => {
+            fs::create_dir_all(dir).context("Failed to create the output directory")?;
+            for share in &shares {
+                let path = dir.join(format!(
+                    "{}-share-{}-of-{}.txt",
+                    &fingerprint[..8],
+                    share.index,
+                    share.shares
+                ));
+                write_private(&path, &format!("{}\n", share))?;
+                println!("Wrote {}", path.display());
+            }
+        }
+        None => {
+            for share in &shares {
+                println!("{}\n", share);
+            }
+        }
+    }
+
+    eprintln!(
+        "Split {} into {} shares, any {} of them rebuild it with `envx key combine`",
+        key, args.shares, args.threshold
+    );
+
+    Ok(())
+}
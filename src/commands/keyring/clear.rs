@@ -1,7 +1,13 @@
-use crate::utils::{config::get_config, keyring::clear_password, prompt::prompt_select};
+use crate::utils::{
+    config::{get_config, get_token_cache_path},
+    keyring::clear_password,
+    prompt::prompt_select,
+};
+use envx::token_cache::TokenCache;
 
 use super::*;
 
+/// Forget the saved password of a key, and the auth tokens signed with it
 #[derive(Parser)]
 pub struct Args {
     /// Partial fingerprint of the key to set
@@ -17,6 +23,12 @@ pub async fn command(args: Args) -> Result<()> {
         None => prompt_select("Select key to clear password", config.keys.clone())?.fingerprint,
     };
 
+    // Even if `cache_tokens` was turned off since they were written
+    TokenCache::default()
+        .persist_to(get_token_cache_path()?)
+        .forget(&fingerprint)
+        .context("Failed to clear cached tokens")?;
+
     clear_password(&fingerprint)?;
 
     Ok(())
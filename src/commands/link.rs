@@ -1,6 +1,7 @@
 use super::*;
 use crate::utils::choice::Choice;
-use crate::utils::config::get_config;
+use crate::utils::config::{get_config, Config};
+use crate::utils::project_file::{project_file_location, ProjectFile, PROJECT_FILE};
 
 /// Get all environment variables for a project
 #[derive(Parser)]
@@ -16,20 +17,59 @@ pub struct Args {
     /// Force a new project to be linked to the current directory, unlinking the current project
     #[clap(short, long)]
     force: bool,
+
+    /// Default environment for this directory (e.g. dev, staging, prod)
+    #[clap(short, long)]
+    env: Option<String>,
+
+    /// Write .envx.toml at the root of the repository instead, to commit it
+    #[clap(long)]
+    file: bool,
+
+    /// Key that must be set for the project to run, with --file (repeatable)
+    #[clap(short, long = "require", requires = "file")]
+    require: Vec<String>,
 }
 
 pub async fn command(args: Args) -> Result<()> {
-    let mut config = get_config()?;
+    if args.file {
+        return link_file(args).await;
+    }
+
+    let config = get_config()?;
 
     let projects = &config.projects;
     let cwd = std::env::current_dir()?;
 
     if let Some(project) = projects.iter().find(|p| p.path == cwd) {
+        let same_project = args
+            .project_id
+            .as_ref()
+            .map_or(true, |p| *p == project.project_id);
+
+        if args.env.is_some() && same_project && !args.force {
+            let project_id = project.project_id.clone();
+            Config::update(|config| {
+                config
+                    .projects
+                    .iter_mut()
+                    .filter(|p| p.path == cwd)
+                    .for_each(|p| p.environment = args.env.clone());
+                Ok(())
+            })?;
+            println!(
+                "{} {} ({})",
+                "Linked environment:".green(),
+                args.env.unwrap_or_default(),
+                project_id
+            );
+            return Ok(());
+        }
+
         if args.force {
             println!("Forced new project");
             println!("Unlinking current project...");
-            let old = config.unset_project()?;
-            config.write()?;
+            let old = Config::update(|config| config.unset_project())?;
             println!(
                 "{} {}",
                 "Unset project(s):".green(),
@@ -51,8 +91,54 @@ pub async fn command(args: Args) -> Result<()> {
         None => Choice::choose_project(&key.fingerprint).await?,
     };
 
-    config.set_project(&project_id)?;
-    config.write()?;
+    Config::update(|config| config.set_project(&project_id, args.env))
+}
+
+/// Create or update .envx.toml, keeping what isn't given on the command line
+async fn link_file(args: Args) -> Result<()> {
+    let path = project_file_location()?;
+    let existing = match ProjectFile::find()? {
+        Some((file, found)) if found == path => Some(file),
+        _ => None,
+    };
+
+    if let (Some(existing), Some(project_id)) = (&existing, &args.project_id) {
+        if existing.project_id != *project_id && !args.force {
+            println!("{} is linked to another project", path.display());
+            println!("  Force a new project with `envx link --file --force`");
+            println!("{} {}", "Current project:".green(), existing.project_id);
+            return Ok(());
+        }
+    }
+
+    let project_id = match (args.project_id, &existing) {
+        (Some(p), _) => p,
+        (None, Some(existing)) => existing.project_id.clone(),
+        (None, None) => {
+            let key = get_config()?.get_key_or_default(args.key)?;
+            Choice::choose_project(&key.fingerprint).await?
+        }
+    };
+
+    let same_project = existing.as_ref().filter(|e| e.project_id == project_id);
+    let file = ProjectFile {
+        environment: args
+            .env
+            .or_else(|| same_project.and_then(|e| e.environment.clone())),
+        required: match args.require.is_empty() {
+            true => same_project.map(|e| e.required.clone()).unwrap_or_default(),
+            false => args.require.iter().map(|k| k.to_uppercase()).collect(),
+        },
+        project_id,
+    };
+    file.write(&path)?;
+
+    println!(
+        "{} {} ({})",
+        format!("Wrote {}:", PROJECT_FILE).green(),
+        path.display(),
+        file.project_id
+    );
 
     Ok(())
 }
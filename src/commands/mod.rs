@@ -6,23 +6,36 @@ pub(super) use colored::Colorize;
 pub mod config;
 pub mod delete;
 pub mod get;
+pub mod key;
 pub mod keyring;
 pub mod new;
 
 // No subcommands
 pub mod add_user_to_project;
 pub mod auth;
+pub mod backup;
 pub mod debug;
 pub mod decrypt;
+pub mod diff;
 pub mod encrypt;
+pub mod environments;
 pub mod export;
 pub mod gen;
+pub mod history;
 pub mod import;
 pub mod link;
+pub mod prune;
+pub mod pull;
+pub mod push;
+pub mod remove_user_from_project;
+pub mod render;
+pub mod restore;
+pub mod rollback;
 pub mod run;
 pub mod set;
 pub mod shell;
 pub mod sign;
+pub mod sync;
 pub mod unlink;
 pub mod unset;
 pub mod upload;
@@ -0,0 +1,192 @@
+use super::*;
+use crate::{
+    sdk::SDK,
+    utils::{choice::Choice, config::get_config, prompt::prompt_confirm},
+};
+use anyhow::bail;
+use chrono::{Duration, Utc};
+use envx::partial_variable::{newest_first, ParsedPartialVariable, ToParsed};
+use futures::stream::{self, StreamExt};
+use std::collections::BTreeMap;
+
+/// Number of deletions running at the same time
+const CONCURRENT_DELETIONS: usize = 8;
+
+/// Delete superseded versions of variables from the server
+#[derive(Parser)]
+pub struct Args {
+    /// Number of versions to keep per variable, the newest one is always kept
+    #[clap(long, default_value_t = 1)]
+    keep: usize,
+
+    /// Only delete versions older than this (e.g. 90d, 12h, 2w)
+    #[clap(long)]
+    older_than: Option<String>,
+
+    /// List the versions that would be deleted without deleting them
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Don't prompt for confirmation
+    #[clap(short, long)]
+    force: bool,
+
+    /// Key to use
+    #[clap(short, long)]
+    key: Option<String>,
+
+    /// Project ID
+    #[clap(short, long)]
+    project_id: Option<String>,
+}
+
+pub async fn command(args: Args) -> Result<()> {
+    let config = get_config()?;
+    if !config.online {
+        bail!("Pruning deletes variables on the server, it is not available in offline mode");
+    }
+
+    let key = config.get_key_or_default(args.key)?;
+    let project_id = Choice::try_project(args.project_id, &key.fingerprint).await?;
+
+    let cutoff = match &args.older_than {
+        Some(d) => Some(
+            Utc::now()
+                .checked_sub_signed(parse_duration(d)?)
+                .with_context(|| format!("Duration too long: {}", d))?,
+        ),
+        None => None,
+    };
+
+    let (kvpairs, partials) = SDK::get_variables(&project_id, &key.fingerprint).await?;
+
+    let mut versions = BTreeMap::<String, Vec<ParsedPartialVariable>>::new();
+    for variable in partials.zip_to_parsed(kvpairs) {
+        versions
+            .entry(variable.value.scoped_key())
+            .or_default()
+            .push(variable);
+    }
+
+    // Same ordering as `DeDupe`, so the version it keeps is never deleted
+    let keep = args.keep.max(1);
+    let stale = versions
+        .values_mut()
+        .flat_map(|history| {
+            history.sort_by(newest_first);
+            history.split_off(keep.min(history.len()))
+        })
+        .filter(|v| match cutoff {
+            Some(cutoff) => v.created_at().is_ok_and(|c| c < cutoff),
+            None => true,
+        })
+        .collect::<Vec<ParsedPartialVariable>>();
+
+    if stale.is_empty() {
+        println!("Nothing to prune");
+        return Ok(());
+    }
+
+    println!("Stale versions:");
+    for variable in stale.iter() {
+        println!(
+            "  {} {} {}",
+            variable.value.scoped_key().bold(),
+            variable.id.dimmed(),
+            variable.created_at
+        );
+    }
+
+    if args.dry_run {
+        println!("{} version(s) would be deleted", stale.len());
+        return Ok(());
+    }
+
+    if !args.force && !prompt_confirm(&format!("Delete {} version(s)?", stale.len()))? {
+        println!("Aborting...");
+        return Ok(());
+    }
+
+    let total = stale.len();
+    let fingerprint = &key.fingerprint;
+    let results = stream::iter(stale.iter())
+        .map(|variable| async move {
+            let result = SDK::delete_variable(&variable.id, fingerprint).await;
+            (variable, result)
+        })
+        .buffer_unordered(CONCURRENT_DELETIONS)
+        .enumerate()
+        .map(|(i, (variable, result))| {
+            match &result {
+                Ok(_) => println!("[{}/{}] Deleted {}", i + 1, total, variable.id),
+                Err(e) => eprintln!(
+                    "[{}/{}] {} {}: {}",
+                    i + 1,
+                    total,
+                    "Failed to delete".red(),
+                    variable.id,
+                    e
+                ),
+            }
+            result
+        })
+        .collect::<Vec<Result<()>>>()
+        .await;
+
+    let failed = results.iter().filter(|r| r.is_err()).count();
+    if failed > 0 {
+        bail!("Failed to delete {} of {} version(s)", failed, total);
+    }
+
+    println!("Pruned {} version(s)", total);
+
+    Ok(())
+}
+
+/// Parse durations like `90d`, `12h`, `30m` or `2w`
+fn parse_duration(duration: &str) -> Result<Duration> {
+    let duration = duration.trim();
+    let split = duration
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(duration.len());
+    let (amount, unit) = duration.split_at(split);
+    let amount = amount
+        .parse::<i64>()
+        .with_context(|| format!("Invalid duration: {}", duration))?;
+
+    let parsed = match unit {
+        "m" => Duration::try_minutes(amount),
+        "h" => Duration::try_hours(amount),
+        "d" | "" => Duration::try_days(amount),
+        "w" => Duration::try_weeks(amount),
+        _ => bail!("Invalid duration unit '{}', use m, h, d or w", unit),
+    };
+    parsed.with_context(|| format!("Duration too long: {}", duration))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_durations() {
+        assert_eq!(parse_duration("90d").unwrap(), Duration::days(90));
+        assert_eq!(parse_duration(" 12h ").unwrap(), Duration::hours(12));
+        assert_eq!(parse_duration("30m").unwrap(), Duration::minutes(30));
+        assert_eq!(parse_duration("2w").unwrap(), Duration::weeks(2));
+        assert_eq!(parse_duration("7").unwrap(), Duration::days(7));
+
+        assert!(parse_duration("d").is_err());
+        assert!(parse_duration("-1d").is_err());
+        assert!(parse_duration("3y").is_err());
+    }
+
+    #[test]
+    fn rejects_durations_that_overflow() {
+        assert!(parse_duration("99999999999999d").is_err());
+        assert!(parse_duration("99999999999999999w").is_err());
+        // Fits in a duration, but not before the current date
+        let long = parse_duration("9999999999d").unwrap();
+        assert!(Utc::now().checked_sub_signed(long).is_none());
+    }
+}
@@ -0,0 +1,96 @@
+use super::*;
+use crate::{
+    sdk::SDK,
+    utils::{
+        choice::Choice,
+        config::get_config,
+        dotenv::{is_unmodified, serialize_with_header},
+        file::add_to_gitignore,
+        local_store::LocalStore,
+        project_file::{ProjectFile, PROJECT_FILE},
+    },
+};
+use anyhow::bail;
+use envx::{file::write_private, kvpair::select_environment};
+use std::{fs, path::PathBuf};
+
+/// Write the variables of a project to a dotenv file
+#[derive(Parser)]
+pub struct Args {
+    /// Key to use
+    #[clap(short, long)]
+    key: Option<String>,
+
+    /// Project ID
+    #[clap(short, long)]
+    project_id: Option<String>,
+
+    /// File to write
+    #[clap(short, long, default_value = ".env")]
+    output: PathBuf,
+
+    /// Overwrite the file even if it was edited since the last pull
+    #[clap(short, long)]
+    force: bool,
+
+    /// Add the file to the .gitignore next to it
+    #[clap(long)]
+    gitignore: bool,
+
+    /// Environment to pull, defaults to the one linked to this directory
+    #[clap(short, long)]
+    env: Option<String>,
+}
+
+pub async fn command(args: Args) -> Result<()> {
+    let config = get_config()?;
+    let key = config.get_key_or_default(args.key)?;
+    let project_id = Choice::try_project(args.project_id, &key.fingerprint).await?;
+    let environment = Choice::try_environment(args.env, &project_id)?;
+
+    if args.output.exists() && !args.force {
+        let existing = fs::read_to_string(&args.output).context("Failed to read output file")?;
+        if !is_unmodified(&existing) {
+            bail!(
+                "{} has local edits or was not written by envx\nUse --force to overwrite it",
+                args.output.display()
+            );
+        }
+    }
+
+    let kvpairs = if config.online {
+        SDK::get_variables_pruned(&project_id, &key.fingerprint).await?
+    } else {
+        LocalStore::get_variables_pruned(&project_id)?
+    };
+    let kvpairs = select_environment(kvpairs, environment.as_deref());
+
+    if let Some(file) = ProjectFile::find_for(&project_id)? {
+        let missing = file.missing(
+            &kvpairs
+                .iter()
+                .map(|k| (k.key.clone(), k.value.clone()))
+                .collect(),
+        );
+        if !missing.is_empty() {
+            eprintln!(
+                "{} {}",
+                format!("Missing required variable(s) listed in {}:", PROJECT_FILE).yellow(),
+                missing.join(", ")
+            );
+        }
+    }
+
+    write_private(&args.output, &serialize_with_header(&kvpairs, &project_id))?;
+    println!(
+        "Wrote {} variables to {}",
+        kvpairs.len(),
+        args.output.display()
+    );
+
+    if args.gitignore && add_to_gitignore(&args.output)? {
+        println!("Added {} to .gitignore", args.output.display());
+    }
+
+    Ok(())
+}
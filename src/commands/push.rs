@@ -0,0 +1,132 @@
+use super::*;
+use crate::{
+    sdk::SDK,
+    utils::{
+        btreemap::ToBTreeMap,
+        choice::Choice,
+        config::get_config,
+        dotenv,
+        local_store::{LocalProject, LocalStore},
+    },
+};
+use envx::kvpair::KVPair;
+use std::{fs, path::Path, path::PathBuf};
+
+/// Upload the variables of a dotenv file to a project
+#[derive(Parser)]
+pub struct Args {
+    /// Dotenv file to read
+    #[clap(default_value = ".env")]
+    file: PathBuf,
+
+    /// Key to use for encryption
+    #[clap(short, long)]
+    key: Option<String>,
+
+    /// Project ID
+    #[clap(short, long)]
+    project_id: Option<String>,
+
+    /// Environment (e.g. dev, staging, prod), defaults to the one linked to this directory
+    #[clap(short, long)]
+    env: Option<String>,
+}
+
+pub async fn command(args: Args) -> Result<()> {
+    let config = get_config()?;
+    let key = config.get_key_or_default(args.key)?;
+    let project_id = Choice::try_project(args.project_id, &key.fingerprint).await?;
+
+    let environment = Choice::try_environment(args.env, &project_id)?;
+
+    let kvpairs = read_dotenv(&args.file)?;
+    push(kvpairs, &key.fingerprint, &project_id, environment).await
+}
+
+pub(super) fn read_dotenv(path: &Path) -> Result<Vec<KVPair>> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    dotenv::parse(&contents).with_context(|| format!("Failed to parse {}", path.display()))
+}
+
+/// Upload the variables that are new or changed in an environment, and print a summary
+pub(super) async fn push(
+    kvpairs: Vec<KVPair>,
+    fingerprint: &str,
+    project_id: &str,
+    environment: Option<String>,
+) -> Result<()> {
+    let config = get_config()?;
+
+    let kvpairs = kvpairs
+        .into_iter()
+        .map(|k| k.with_environment(environment.clone()))
+        .collect::<Vec<KVPair>>();
+
+    let existing = if config.online {
+        SDK::get_variables_pruned(project_id, fingerprint).await?
+    } else {
+        LocalStore::get_variables_pruned(project_id)?
+    }
+    .into_iter()
+    .filter(|k| k.environment == environment)
+    .collect::<Vec<KVPair>>()
+    .to_btreemap()?;
+
+    let mut added = vec![];
+    let mut changed = vec![];
+    let mut unchanged = vec![];
+    for kvpair in kvpairs.iter() {
+        match existing.get(&kvpair.key) {
+            None => added.push(kvpair.key.as_str()),
+            Some(value) if *value != kvpair.value => changed.push(kvpair.key.as_str()),
+            Some(_) => unchanged.push(kvpair.key.as_str()),
+        }
+    }
+
+    for (label, keys) in [
+        ("Added:".green(), &added),
+        ("Changed:".yellow(), &changed),
+        ("Unchanged:".dimmed(), &unchanged),
+    ] {
+        if !keys.is_empty() {
+            println!("{} {}", label, keys.join(", "));
+        }
+    }
+
+    let to_upload = kvpairs
+        .iter()
+        .filter(|k| !unchanged.contains(&k.key.as_str()))
+        .cloned()
+        .collect::<Vec<KVPair>>();
+
+    if to_upload.is_empty() {
+        println!("Nothing to upload");
+        return Ok(());
+    }
+
+    let keys = to_upload
+        .iter()
+        .map(|k| k.scoped_key())
+        .collect::<Vec<String>>();
+
+    if !config.online {
+        let ids = LocalStore::set_many(to_upload, fingerprint, project_id)?;
+        LocalProject::clear_rotation(project_id, &keys)?;
+        println!("Saved {} variables locally", ids.len());
+        println!("Run `envx sync` when you are back online to upload them");
+        return Ok(());
+    }
+
+    let ids = SDK::set_many(to_upload, fingerprint, project_id).await?;
+    LocalProject::clear_rotation(project_id, &keys)?;
+    println!(
+        "Uploaded {} variables ({} added, {} changed, {} unchanged)",
+        ids.len(),
+        added.len(),
+        changed.len(),
+        unchanged.len()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,115 @@
+use super::*;
+use crate::{
+    sdk::SDK,
+    utils::{
+        choice::Choice, config::get_config, local_store::LocalProject,
+        prompt::prompt_confirm_with_default, prompt::prompt_select,
+    },
+};
+use anyhow::bail;
+
+/// Remove a user from a project and re-encrypt its variables without them
+#[derive(Parser)]
+pub struct Args {
+    /// Key to sign with
+    #[clap(short, long)]
+    key: Option<String>,
+
+    /// Project ID to remove user from
+    #[clap(short, long)]
+    project_id: Option<String>,
+
+    /// User ID to remove from project
+    #[clap(short, long)]
+    user_id: Option<String>,
+
+    /// Flag every variable of the project for rotation without prompting
+    ///
+    /// Flags are kept on this machine only, other members don't see them
+    #[clap(long)]
+    rotate: bool,
+}
+
+pub async fn command(args: Args) -> Result<()> {
+    let config = get_config()?;
+    let key = config.get_key_or_default(args.key)?;
+
+    let uuid = config
+        .key_uuid(&key)?
+        .context("Key does not have a UUID, try `envx upload`")?;
+
+    let project_id = Choice::try_project(args.project_id, &key.fingerprint).await?;
+    let project_info = SDK::get_project_info(&project_id, &key.fingerprint).await?;
+
+    let user_id = match args.user_id {
+        Some(u) => u.trim().to_string(),
+        None => {
+            let others = project_info
+                .users
+                .iter()
+                .filter(|u| u.id != uuid)
+                .map(|u| format!("{} - {}", u.id, u.username))
+                .collect::<Vec<String>>();
+            if others.is_empty() {
+                bail!("You are the only member of project {}", project_id);
+            }
+            let selected = prompt_select("Select user to remove", others)?;
+            selected
+                .split(" - ")
+                .next()
+                .context("Failed to get user ID")?
+                .to_string()
+        }
+    };
+
+    if user_id == uuid {
+        bail!("You can't remove yourself from a project");
+    }
+
+    if !project_info.users.iter().any(|u| u.id == user_id) {
+        bail!("User {} is not a member of project {}", user_id, project_id);
+    }
+
+    // Re-encrypt before removing, so a failure can't leave values readable by someone who is
+    // no longer a member. Running the command again finishes the job
+    let recipients = project_info
+        .users
+        .iter()
+        .filter(|u| u.id != user_id)
+        .map(|u| u.public_key.clone())
+        .collect::<Vec<String>>();
+
+    let res = SDK::reencrypt_variables(&project_id, &key.fingerprint, &recipients).await?;
+    println!(
+        "Re-encrypted {} variables for the remaining {} member(s)",
+        res.len(),
+        recipients.len()
+    );
+
+    SDK::remove_user_from_project(&key.fingerprint, &user_id, &project_id).await?;
+    println!("Removed user {} from project {}", user_id, project_id);
+
+    println!();
+    println!(
+        "{}",
+        "The removed user may still hold copies of the values they could read.".yellow()
+    );
+    println!("Secrets such as passwords and API tokens should be rotated.");
+
+    let rotate = args.rotate
+        || prompt_confirm_with_default("Flag every variable for rotation on this machine?", true)?;
+    if rotate {
+        let keys = SDK::get_variables_pruned(&project_id, &key.fingerprint)
+            .await?
+            .into_iter()
+            .map(|k| k.scoped_key())
+            .collect::<Vec<String>>();
+        LocalProject::flag_for_rotation(&project_id, &keys)?;
+        println!(
+            "Flagged {} variable(s) on this machine, setting a new value clears the flag",
+            keys.len()
+        );
+    }
+
+    Ok(())
+}
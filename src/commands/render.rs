@@ -0,0 +1,78 @@
+use super::*;
+use crate::{
+    sdk::SDK,
+    utils::{
+        choice::Choice, config::get_config, interpolate::interpolate, local_store::LocalStore,
+        template::render,
+    },
+};
+use envx::{file::write_private, kvpair::select_environment};
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
+/// Fill {{ KEY }} placeholders in a template with the variables of a project
+#[derive(Parser)]
+pub struct Args {
+    /// Template to render
+    template: PathBuf,
+
+    /// File to write, prints to stdout if omitted
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+
+    /// Key to use
+    #[clap(short, long)]
+    key: Option<String>,
+
+    /// Project ID
+    #[clap(short, long)]
+    project_id: Option<String>,
+
+    /// Environment (e.g. dev, staging, prod), defaults to the one linked to this directory
+    #[clap(short, long)]
+    env: Option<String>,
+
+    /// Use values verbatim instead of expanding ${NAME} references
+    #[clap(long)]
+    no_interpolate: bool,
+}
+
+pub async fn command(args: Args) -> Result<()> {
+    let config = get_config()?;
+    let key = config.get_key_or_default(args.key)?;
+    let project_id = Choice::try_project(args.project_id, &key.fingerprint).await?;
+    let environment = Choice::try_environment(args.env, &project_id)?;
+
+    let template = fs::read_to_string(&args.template)
+        .with_context(|| format!("Failed to read {}", args.template.display()))?;
+
+    let kvpairs = if config.online {
+        SDK::get_variables_pruned(&project_id, &key.fingerprint).await?
+    } else {
+        LocalStore::get_variables_pruned(&project_id)?
+    };
+
+    let mut variables = select_environment(kvpairs, environment.as_deref())
+        .into_iter()
+        .map(|k| (k.key, k.value))
+        .collect::<BTreeMap<String, String>>();
+    if !args.no_interpolate {
+        variables = interpolate(&variables)?;
+    }
+
+    let rendered = render(&template, &variables)
+        .with_context(|| format!("Failed to render {}", args.template.display()))?;
+
+    match args.output {
+        Some(output) => {
+            write_private(&output, &rendered)?;
+            eprintln!(
+                "Rendered {} to {}",
+                args.template.display(),
+                output.display()
+            );
+        }
+        None => print!("{}", rendered),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,44 @@
+use super::*;
+use crate::utils::{backup::Backup, config::Config, prompt::prompt_password};
+use std::{fs, path::PathBuf};
+
+/// Restore the keys and config of a backup, keeping the keys already here
+#[derive(Parser)]
+pub struct Args {
+    /// Backup made with `envx backup`
+    path: PathBuf,
+
+    /// Passphrase the backup was encrypted with
+    #[clap(short, long)]
+    passphrase: Option<String>,
+}
+
+pub async fn command(args: Args) -> Result<()> {
+    let armored = fs::read_to_string(&args.path)
+        .with_context(|| format!("Failed to read {}", args.path.display()))?;
+
+    let passphrase = match args.passphrase {
+        Some(p) => p,
+        None => prompt_password("Backup passphrase")?,
+    };
+    let backup = Backup::decrypt(&armored, passphrase)?;
+    println!(
+        "Backup from {}",
+        backup.created_at.format("%Y-%m-%d %H:%M:%S UTC")
+    );
+
+    let restored =
+        Config::update(|config| backup.restore(config)).context("Failed to restore the backup")?;
+
+    for fingerprint in &restored.added {
+        println!("{} {}", "Restored".green(), fingerprint);
+    }
+    for fingerprint in &restored.completed {
+        println!("{} {}", "Added the secret key of".green(), fingerprint);
+    }
+    for fingerprint in &restored.kept {
+        println!("{} {}, it's already here", "Kept".dimmed(), fingerprint);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,162 @@
+use super::*;
+use crate::{
+    sdk::SDK,
+    utils::{choice::Choice, config::get_config, local_store::LocalStore, prompt::prompt_confirm},
+};
+use anyhow::bail;
+use envx::{
+    kvpair::{mask, KVPair},
+    partial_variable::{newest_first, parse_timestamp, ParsedPartialVariable, ToParsed},
+};
+use std::collections::BTreeMap;
+
+/// Restore a previous version of a variable, or of every variable at a point in time
+#[derive(Parser)]
+pub struct Args {
+    /// Name of the variable, required unless --at is used
+    #[clap(required_unless_present = "at")]
+    variable: Option<String>,
+
+    /// ID of the version to restore (see `envx history`)
+    #[clap(long, conflicts_with_all = ["steps", "at"])]
+    to: Option<String>,
+
+    /// Number of versions to go back
+    #[clap(long, conflicts_with = "at")]
+    steps: Option<usize>,
+
+    /// Restore the values as they were at this time (e.g. 2026-10-01T12:00, UTC)
+    #[clap(long)]
+    at: Option<String>,
+
+    /// Key to use
+    #[clap(short, long)]
+    key: Option<String>,
+
+    /// Project ID
+    #[clap(short, long)]
+    project_id: Option<String>,
+
+    /// Don't prompt for confirmation
+    #[clap(short, long)]
+    force: bool,
+
+    /// Show values in plain text instead of masking them
+    #[clap(short, long)]
+    reveal: bool,
+
+    /// Only roll back variables of this environment, defaults to the one linked to this directory
+    #[clap(short, long)]
+    env: Option<String>,
+}
+
+pub async fn command(args: Args) -> Result<()> {
+    let config = get_config()?;
+    let key = config.get_key_or_default(args.key)?;
+    let project_id = Choice::try_project(args.project_id, &key.fingerprint).await?;
+    let environment = Choice::try_environment(args.env, &project_id)?;
+
+    let (kvpairs, partials) = if config.online {
+        SDK::get_variables(&project_id, &key.fingerprint).await?
+    } else {
+        LocalStore::get_variables(&project_id)?
+    };
+
+    // Every version of every variable, newest first
+    let mut versions = BTreeMap::<String, Vec<ParsedPartialVariable>>::new();
+    for variable in partials.zip_to_parsed(kvpairs) {
+        if environment.is_some() && variable.value.environment != environment {
+            continue;
+        }
+        versions
+            .entry(variable.value.scoped_key())
+            .or_default()
+            .push(variable);
+    }
+    versions.values_mut().for_each(|v| v.sort_by(newest_first));
+
+    if let Some(name) = &args.variable {
+        let name = KVPair::new(name.to_uppercase(), String::new())
+            .with_environment(environment.clone())
+            .scoped_key();
+        versions.retain(|k, _| *k == name);
+        if versions.is_empty() {
+            bail!("No versions of {} found in project {}", name, project_id);
+        }
+    }
+
+    let mut restore = vec![];
+    for (name, history) in versions.iter() {
+        let current = &history[0];
+        let target = match (&args.to, &args.at) {
+            (Some(id), _) => history
+                .iter()
+                .find(|v| v.id == *id)
+                .with_context(|| format!("No version {} of {}", id, name))?,
+            (_, Some(at)) => {
+                let at = parse_timestamp(at).context("Invalid --at timestamp")?;
+                let target = history
+                    .iter()
+                    .find(|v| v.created_at().is_ok_and(|c| c <= at));
+                match target {
+                    Some(t) => t,
+                    None => {
+                        println!(
+                            "{} did not exist at that time, left unchanged (use `envx unset` to remove it)",
+                            name
+                        );
+                        continue;
+                    }
+                }
+            }
+            _ => {
+                let steps = args.steps.unwrap_or(1);
+                history.get(steps).with_context(|| {
+                    format!("{} only has {} older version(s)", name, history.len() - 1)
+                })?
+            }
+        };
+
+        if target.value.value == current.value.value {
+            continue;
+        }
+
+        let (from, to) = if args.reveal {
+            (current.value.value.clone(), target.value.value.clone())
+        } else {
+            (mask(&current.value.value), mask(&target.value.value))
+        };
+        println!(
+            "{}: {} -> {} (from {}, {})",
+            name.bold(),
+            from,
+            to,
+            target.id.dimmed(),
+            target.created_at
+        );
+        restore.push(target.value.clone());
+    }
+
+    if restore.is_empty() {
+        println!("Nothing to roll back");
+        return Ok(());
+    }
+
+    if !args.force && !prompt_confirm(&format!("Restore {} variable(s)?", restore.len()))? {
+        println!("Aborting...");
+        return Ok(());
+    }
+
+    if !config.online {
+        let ids = LocalStore::set_many(restore, &key.fingerprint, &project_id)?;
+        println!("Restored {} variables locally", ids.len());
+        println!("Run `envx sync` when you are back online to upload them");
+        return Ok(());
+    }
+
+    let ids = SDK::set_many(restore, &key.fingerprint, &project_id).await?;
+    println!("Restored {} variables", ids.len());
+    println!("IDs: {:?}", ids);
+
+    Ok(())
+}
@@ -1,10 +1,17 @@
 use anyhow::bail;
+use std::path::PathBuf;
 
+use super::push::{push, read_dotenv};
 use super::*;
 use crate::{
     sdk::SDK,
-    utils::{choice::Choice, config::get_config, kvpair::KVPair},
+    utils::{
+        choice::Choice,
+        config::get_config,
+        local_store::{LocalProject, LocalStore},
+    },
 };
+use envx::kvpair::KVPair;
 
 /// Set a variable
 #[derive(Parser)]
@@ -20,23 +27,27 @@ pub struct Args {
     /// Project ID
     #[clap(short, long)]
     project_id: Option<String>,
+
+    /// Read variables from a dotenv file, KVPairs given as arguments take precedence
+    #[clap(long)]
+    from_file: Option<PathBuf>,
+
+    /// Environment (e.g. dev, staging, prod), defaults to the one linked to this directory
+    #[clap(short, long)]
+    env: Option<String>,
 }
 
 pub async fn command(args: Args) -> Result<()> {
-    if args.kvpairs.is_empty() {
+    if args.kvpairs.is_empty() && args.from_file.is_none() {
         bail!(
             "{}\n{}",
             "No KV pairs provided".red(),
-            "Usage: envx set key=value [key=value]...",
+            "Usage: envx set key=value [key=value]... or envx set --from-file .env",
         );
     }
 
     let config = get_config()?;
-    let key = match &args.key {
-        Some(k) => k,
-        None => &config.primary_key,
-    };
-    let key = config.get_key(key)?;
+    let key = config.get_key_or_default(args.key)?;
 
     let project_id = Choice::try_project(args.project_id, &key.fingerprint).await?;
 
@@ -44,12 +55,17 @@ pub async fn command(args: Args) -> Result<()> {
         return Err(anyhow::anyhow!("No project ID provided"));
     }
 
+    let environment = Choice::try_environment(args.env, &project_id)?;
+
     let (kvpairs, errors): (Vec<KVPair>, Vec<String>) =
         args.kvpairs
             .iter()
             .fold((Vec::new(), Vec::new()), |(mut ok, mut err), k| {
                 match k.split_once('=') {
-                    Some((key, value)) => ok.push(KVPair::new(key.to_uppercase(), value.into())),
+                    Some((key, value)) => ok.push(
+                        KVPair::new(key.to_uppercase(), value.into())
+                            .with_environment(environment.clone()),
+                    ),
                     None => err.push(format!("Invalid KVPair: {}", k)),
                 }
                 (ok, err)
@@ -57,11 +73,33 @@ pub async fn command(args: Args) -> Result<()> {
 
     errors.iter().for_each(|e| println!("Skipping {}", e));
 
+    if let Some(path) = args.from_file {
+        let mut from_file = read_dotenv(&path)?;
+        from_file.retain(|f| !kvpairs.iter().any(|k| k.key == f.key));
+        from_file.extend(kvpairs);
+        return push(from_file, &key.fingerprint, &project_id, environment).await;
+    }
+
     if kvpairs.is_empty() {
         return Err(anyhow::anyhow!("No valid KV pairs provided"));
     }
 
+    let keys = kvpairs
+        .iter()
+        .map(|k| k.scoped_key())
+        .collect::<Vec<String>>();
+
+    if !config.online {
+        let ids = LocalStore::set_many(kvpairs, &key.fingerprint, &project_id)?;
+        LocalProject::clear_rotation(&project_id, &keys)?;
+
+        println!("Saved {} variables locally", ids.len());
+        println!("Run `envx sync` when you are back online to upload them");
+        return Ok(());
+    }
+
     let ids = SDK::set_many(kvpairs, &key.fingerprint, &project_id).await?;
+    LocalProject::clear_rotation(&project_id, &keys)?;
 
     println!("Uploaded {} variables", ids.len());
     println!("IDs: {:?}", ids);
@@ -1,4 +1,8 @@
-use crate::utils::choice::Choice;
+use crate::utils::{
+    choice::Choice, interpolate::interpolate, local_store::LocalStore,
+    project_file::ensure_required,
+};
+use envx::kvpair::select_environment;
 
 use super::*;
 use std::collections::BTreeMap;
@@ -34,17 +38,21 @@ pub struct Args {
     #[clap(short, long)]
     key: Option<String>,
 
+    /// Environment (e.g. dev, staging, prod), defaults to the one linked to this directory
+    #[clap(short, long)]
+    env: Option<String>,
+
+    /// Pass values verbatim instead of expanding ${NAME} references
+    #[clap(long)]
+    no_interpolate: bool,
+
     #[clap(short, long)]
     silent: bool,
 }
 
 pub async fn command(args: Args) -> Result<()> {
     let config = crate::utils::config::get_config()?;
-    let key = match args.key {
-        Some(k) => k.to_owned(),
-        None => config.primary_key.clone(),
-    };
-    let key = config.get_key(&key)?;
+    let key = config.get_key_or_default(args.key)?;
 
     let project_id = Choice::try_project(args.project_id, &key.fingerprint).await?;
 
@@ -52,14 +60,23 @@ pub async fn command(args: Args) -> Result<()> {
         return Err(anyhow::anyhow!("No project ID provided"));
     }
 
-    let mut all_variables = BTreeMap::<String, String>::new();
-    all_variables.insert("IN_ENVCLI_SHELL".to_owned(), "true".to_owned());
+    let environment = Choice::try_environment(args.env, &project_id)?;
 
-    let variables = crate::sdk::SDK::get_variables_pruned(&project_id, &key.fingerprint).await?;
+    let variables = if config.online {
+        crate::sdk::SDK::get_variables_pruned(&project_id, &key.fingerprint).await?
+    } else {
+        LocalStore::get_variables_pruned(&project_id)?
+    };
 
-    for variable in variables {
-        all_variables.insert(variable.key, variable.value);
+    let mut all_variables = select_environment(variables, environment.as_deref())
+        .into_iter()
+        .map(|variable| (variable.key, variable.value))
+        .collect::<BTreeMap<String, String>>();
+    if !args.no_interpolate {
+        all_variables = interpolate(&all_variables)?;
     }
+    ensure_required(&project_id, &all_variables)?;
+    all_variables.insert("IN_ENVCLI_SHELL".to_owned(), "true".to_owned());
 
     let shell = std::env::var("SHELL").unwrap_or(match std::env::consts::OS {
         "windows" => match windows_shell_detection().await {
@@ -0,0 +1,368 @@
+use super::*;
+use crate::{
+    sdk::SDK,
+    utils::{
+        choice::Choice,
+        config::get_config,
+        local_store::{decrypt_local, LocalProject, LocalVariable, SyncState},
+    },
+};
+use anyhow::bail;
+use chrono::Utc;
+use clap::ValueEnum;
+use envx::{
+    kvpair::KVPair,
+    partial_variable::{DeDupe, ParsedPartialVariable, ToParsed},
+};
+use std::collections::HashMap;
+
+/// Reconcile the offline store with the server
+#[derive(Parser)]
+pub struct Args {
+    /// Key to use
+    #[clap(short, long)]
+    key: Option<String>,
+
+    /// Project ID
+    #[clap(short, long)]
+    project_id: Option<String>,
+
+    /// Sync every project in the offline store
+    #[clap(short, long)]
+    all: bool,
+
+    /// Which side wins when a variable changed both offline and on the server
+    #[clap(long, value_enum)]
+    prefer: Option<Prefer>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Prefer {
+    Local,
+    Remote,
+}
+
+pub async fn command(args: Args) -> Result<()> {
+    let config = get_config()?;
+    let key = config.get_key_or_default(args.key)?;
+
+    let project_ids = if args.all {
+        LocalProject::list()?
+            .into_iter()
+            .map(|p| p.project_id)
+            .collect::<Vec<String>>()
+    } else {
+        vec![Choice::try_project(args.project_id, &key.fingerprint).await?]
+    };
+
+    let mut unresolved = 0;
+    for project_id in project_ids {
+        println!("Syncing project {}...", project_id);
+        unresolved += sync_project(&project_id, &key.fingerprint, args.prefer).await?;
+    }
+
+    if unresolved > 0 {
+        bail!(
+            "{} conflict(s) left unresolved, re-run with `--prefer local` or `--prefer remote`",
+            unresolved
+        );
+    }
+
+    Ok(())
+}
+
+/// Returns the number of unresolved conflicts
+async fn sync_project(
+    project_id: &str,
+    fingerprint: &str,
+    prefer: Option<Prefer>,
+) -> Result<usize> {
+    let mut local = LocalProject::read(project_id)?;
+    let project_info = SDK::get_project_info(project_id, fingerprint).await?;
+
+    let (remote_kvpairs, remote_partials) = SDK::get_variables(project_id, fingerprint).await?;
+    let remote_versions = remote_partials.zip_to_parsed(remote_kvpairs);
+
+    let all_local = local.variables.iter().collect::<Vec<&LocalVariable>>();
+    let decrypted = decrypt_local(&all_local)?;
+
+    let Plan {
+        to_upload,
+        to_delete,
+        kept,
+        conflicts,
+    } = plan(&local.variables, &decrypted, &remote_versions, prefer);
+
+    if !to_upload.is_empty() {
+        let ids = SDK::set_many(to_upload, fingerprint, project_id).await?;
+        println!("  Uploaded {} variable(s)", ids.len());
+    }
+
+    for id in to_delete.iter() {
+        SDK::delete_variable(id, fingerprint).await?;
+    }
+    if !to_delete.is_empty() {
+        println!("  Deleted {} variable(s)", to_delete.len());
+    }
+
+    for name in conflicts.iter() {
+        println!(
+            "  {} {} changed both offline and on the server",
+            "Conflict:".red(),
+            name
+        );
+    }
+
+    let encrypted = SDK::get_variables_encrypted(project_id, fingerprint).await?;
+    local.variables = encrypted
+        .into_iter()
+        .map(|e| LocalVariable {
+            id: e.id,
+            value: e.value,
+            project_id: e.project_id,
+            created_at: e.created_at,
+            state: SyncState::Synced,
+            conflict: false,
+        })
+        .chain(kept)
+        .collect();
+    local.recipients = project_info
+        .users
+        .iter()
+        .map(|u| u.public_key.clone())
+        .collect();
+    local.last_sync = Some(Utc::now().to_rfc3339());
+    local.write()?;
+
+    println!("  {} variable(s) stored offline", local.visible().len());
+
+    Ok(conflicts.len())
+}
+
+/// What a sync does with the offline changes of a project
+#[derive(Debug, Default)]
+struct Plan {
+    to_upload: Vec<KVPair>,
+    to_delete: Vec<String>,
+    /// Conflicting changes, kept offline until resolved
+    kept: Vec<LocalVariable>,
+    conflicts: Vec<String>,
+}
+
+/// `decrypted` are the values of `local`, `remote_versions` every version on the server
+fn plan(
+    local: &[LocalVariable],
+    decrypted: &[KVPair],
+    remote_versions: &Vec<ParsedPartialVariable>,
+    prefer: Option<Prefer>,
+) -> Plan {
+    // The newest version of every key (per environment) on the server
+    let remote = remote_versions
+        .dedupe()
+        .into_iter()
+        .map(|p| (p.value.scoped_key(), p))
+        .collect::<HashMap<_, _>>();
+
+    // Newest synced version of every key, ie. what the server held when we went offline
+    let mut base = HashMap::<String, (&LocalVariable, &KVPair)>::new();
+    // Newest offline change of every key
+    let mut changes = HashMap::<String, (&LocalVariable, &KVPair)>::new();
+    // Every version of a key deleted offline
+    let mut deleted = HashMap::<String, Vec<&str>>::new();
+    for (variable, kvpair) in local.iter().zip(decrypted.iter()) {
+        if variable.state == SyncState::Deleted {
+            deleted
+                .entry(kvpair.scoped_key())
+                .or_default()
+                .push(&variable.id);
+        }
+        let map = match variable.state {
+            SyncState::Synced => &mut base,
+            SyncState::Pending | SyncState::Deleted => &mut changes,
+        };
+        match map.get(&kvpair.scoped_key()) {
+            Some((existing, _)) if existing.created_at >= variable.created_at => {}
+            _ => {
+                map.insert(kvpair.scoped_key(), (variable, kvpair));
+            }
+        }
+    }
+
+    let mut plan = Plan::default();
+    for (name, (variable, kvpair)) in changes {
+        let remote_value = remote.get(&name).map(|r| &r.value.value);
+        let base_value = base.get(&name).map(|(_, b)| &b.value);
+
+        // A deletion conflicts with versions set on the server since we went offline
+        let conflicting = match variable.state {
+            SyncState::Deleted => remote
+                .get(&name)
+                .is_some_and(|r| !deleted[&name].contains(&r.id.as_str())),
+            _ => {
+                remote_value != Some(&kvpair.value)
+                    && (variable.conflict || remote_value != base_value)
+            }
+        };
+
+        let apply = match (conflicting, prefer) {
+            (false, _) | (true, Some(Prefer::Local)) => true,
+            (true, Some(Prefer::Remote)) => false,
+            (true, None) => {
+                plan.conflicts.push(name);
+                plan.kept.push(LocalVariable {
+                    conflict: true,
+                    ..variable.clone()
+                });
+                continue;
+            }
+        };
+
+        if !apply {
+            continue;
+        }
+
+        match variable.state {
+            SyncState::Deleted => plan.to_delete.extend(versions_of(&name, remote_versions)),
+            _ => {
+                if remote_value != Some(&kvpair.value) {
+                    plan.to_upload.push(kvpair.clone());
+                }
+            }
+        }
+    }
+
+    plan
+}
+
+/// IDs of every version of a key on the server
+///
+/// Deleting only the newest one would bring an older version back on the next pull
+fn versions_of(name: &str, remote: &[ParsedPartialVariable]) -> Vec<String> {
+    remote
+        .iter()
+        .filter(|r| r.value.scoped_key() == name)
+        .map(|r| r.id.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(
+        id: &str,
+        key: &str,
+        environment: Option<&str>,
+        created_at: &str,
+    ) -> ParsedPartialVariable {
+        ParsedPartialVariable {
+            id: id.to_string(),
+            value: KVPair::new(key.to_string(), id.to_string())
+                .with_environment(environment.map(String::from)),
+            project_id: "project".to_string(),
+            created_at: created_at.to_string(),
+        }
+    }
+
+    #[test]
+    fn deletes_every_version_of_a_key() {
+        let remote = vec![
+            version("1", "TOKEN", None, "2024-01-01T00:00:00Z"),
+            version("2", "OTHER", None, "2024-01-02T00:00:00Z"),
+            version("3", "TOKEN", None, "2024-01-03T00:00:00Z"),
+            version("4", "TOKEN", Some("prod"), "2024-01-04T00:00:00Z"),
+            version("5", "TOKEN", None, "2024-01-05T00:00:00Z"),
+        ];
+
+        let mut ids = versions_of("TOKEN", &remote);
+        ids.sort();
+        assert_eq!(ids, ["1", "3", "5"]);
+
+        // Once they are all gone, deduping what is left doesn't bring the key back
+        let left = remote
+            .into_iter()
+            .filter(|r| !ids.contains(&r.id))
+            .collect::<Vec<ParsedPartialVariable>>()
+            .dedupe();
+        assert!(left.iter().all(|r| r.value.scoped_key() != "TOKEN"));
+        assert_eq!(versions_of("prod:TOKEN", &left), ["4"]);
+    }
+
+    fn synced(version: &ParsedPartialVariable) -> LocalVariable {
+        LocalVariable {
+            id: version.id.clone(),
+            value: "<armored>".to_string(),
+            project_id: version.project_id.clone(),
+            created_at: version.created_at.clone(),
+            state: SyncState::Synced,
+            conflict: false,
+        }
+    }
+
+    #[test]
+    fn unset_offline_then_sync() {
+        let remote = vec![
+            version("1", "TOKEN", None, "2024-01-01T00:00:00Z"),
+            version("2", "OTHER", None, "2024-01-02T00:00:00Z"),
+            version("3", "TOKEN", None, "2024-01-03T00:00:00Z"),
+        ];
+        let mut local = LocalProject::new("project");
+        local.variables = remote.iter().map(synced).collect();
+        let mut kvpairs = remote
+            .iter()
+            .map(|r| r.value.clone())
+            .collect::<Vec<KVPair>>();
+
+        // A version set offline, then the key is unset by its oldest version
+        local.variables.push(LocalVariable {
+            id: "local-4".to_string(),
+            state: SyncState::Pending,
+            created_at: "2024-01-04T00:00:00Z".to_string(),
+            ..synced(&remote[0])
+        });
+        kvpairs.push(KVPair::new("TOKEN".to_string(), "4".to_string()));
+        local.delete("1", &kvpairs).unwrap();
+
+        // The pending version is gone, the others are hidden
+        assert_eq!(local.variables.len(), 3);
+        let visible = local.visible();
+        assert_eq!(visible.len(), 1);
+        assert_eq!(visible[0].id, "2");
+
+        // No older version of TOKEN comes back
+        let kvpairs = local
+            .variables
+            .iter()
+            .map(|v| remote.iter().find(|r| r.id == v.id).unwrap().value.clone())
+            .collect::<Vec<KVPair>>();
+        let shown = visible
+            .iter()
+            .map(|v| v.to_partial())
+            .collect::<Vec<_>>()
+            .zip_to_parsed(vec![kvpairs[1].clone()])
+            .dedupe();
+        assert_eq!(shown.len(), 1);
+        assert_eq!(shown[0].value.key, "OTHER");
+
+        // Nothing changed on the server, so deleting isn't a conflict
+        let mut plan = plan(&local.variables, &kvpairs, &remote, None);
+        plan.to_delete.sort();
+        assert!(plan.conflicts.is_empty());
+        assert!(plan.to_upload.is_empty());
+        assert_eq!(plan.to_delete, ["1", "3"]);
+    }
+
+    #[test]
+    fn unset_offline_conflicts_with_a_newer_remote_version() {
+        let mut remote = vec![version("1", "TOKEN", None, "2024-01-01T00:00:00Z")];
+        let mut local = LocalProject::new("project");
+        local.variables = remote.iter().map(synced).collect();
+        let kvpairs = vec![remote[0].value.clone()];
+        local.delete("1", &kvpairs).unwrap();
+
+        remote.push(version("2", "TOKEN", None, "2024-01-02T00:00:00Z"));
+        let plan = plan(&local.variables, &kvpairs, &remote, None);
+        assert_eq!(plan.conflicts, ["TOKEN"]);
+        assert!(plan.to_delete.is_empty());
+    }
+}
@@ -1,15 +1,12 @@
 use super::*;
-use crate::utils::config::get_config;
+use crate::utils::config::Config;
 
 /// Unset the current project
 #[derive(Parser)]
 pub struct Args {}
 
 pub async fn command(_args: Args) -> Result<()> {
-    let mut config = get_config()?;
-
-    let unset = config.unset_project()?;
-    config.write()?;
+    let unset = Config::update(|config| config.unset_project())?;
 
     // There should only ever be one project unset
     // but the unset command unsets all projects that match the current directory
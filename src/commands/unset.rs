@@ -1,5 +1,5 @@
 use super::*;
-use crate::utils::prompt;
+use crate::utils::{local_store::LocalStore, prompt};
 use crate::{sdk::SDK, utils::config::get_config};
 use anyhow::Context;
 
@@ -13,6 +13,10 @@ pub struct Args {
     /// Key to use
     #[clap(short, long)]
     key: Option<String>,
+
+    /// Only offer variables of this environment for deletion
+    #[clap(short, long)]
+    env: Option<String>,
 }
 
 pub async fn command(args: Args) -> Result<()> {
@@ -22,11 +26,27 @@ pub async fn command(args: Args) -> Result<()> {
     let variable = match args.variable {
         Some(v) => v,
         None => {
-            let (_, all_variables) = SDK::get_all_variables(&key.fingerprint).await?;
+            let (_, mut all_variables) = if config.online {
+                SDK::get_all_variables(&key.fingerprint).await?
+            } else {
+                LocalStore::get_all_variables()?
+            };
+            if args.env.is_some() {
+                all_variables.retain(|v| v.value.environment == args.env);
+            }
             prompt::prompt_options("Select variables to delete", all_variables)?.id
         }
     };
 
+    if !config.online {
+        LocalStore::delete_variable(&variable)?;
+        println!(
+            "Deleted {} locally, run `envx sync` to apply it on the server",
+            variable
+        );
+        return Ok(());
+    }
+
     SDK::delete_variable(&variable, &key.fingerprint).await?;
 
     Ok(())
@@ -1,10 +1,15 @@
 use super::*;
 use crate::{
     sdk::SDK,
-    utils::{config::get_config, prompt::prompt_text},
+    utils::{
+        config::{get_config, Config},
+        prompt::prompt_text,
+    },
 };
 
 /// If your key is not in the database, use this command to upload it
+///
+/// Only registers the key on the active profile
 #[derive(Parser)]
 pub struct Args {
     /// Key to sign with
@@ -17,9 +22,7 @@ pub struct Args {
 }
 
 pub async fn command(args: Args) -> Result<()> {
-    let mut config = get_config()?;
-
-    let key = config.get_key(&args.key)?;
+    let key = get_config()?.get_key(&args.key)?;
 
     let username = match args.username {
         Some(u) => u,
@@ -29,13 +32,5 @@ pub async fn command(args: Args) -> Result<()> {
     let id = SDK::new_user(&username, &key.public_key()?).await?;
     println!("UUID: {}", &id);
 
-    for k in config.keys.iter_mut() {
-        if k.fingerprint == key.fingerprint {
-            k.uuid = Some(id.clone());
-        }
-    }
-
-    config.write()?;
-
-    Ok(())
+    Config::update(|config| config.set_key_uuid(&key.fingerprint, &id))
 }
@@ -1,8 +1,15 @@
 use super::*;
 use crate::{
     sdk::SDK,
-    utils::{btreemap::ToBTreeMap, choice::Choice, config::get_config, table::Table},
+    utils::{
+        btreemap::ToBTreeMap,
+        choice::Choice,
+        config::get_config,
+        local_store::{LocalProject, LocalStore},
+        table::Table,
+    },
 };
+use envx::kvpair::select_environment;
 /// Get all environment variables for the current configured directory
 #[derive(Parser)]
 pub struct Args {
@@ -12,6 +19,10 @@ pub struct Args {
     #[clap(short, long)]
     project_id: Option<String>,
 
+    /// Environment (e.g. dev, staging, prod), defaults to the one linked to this directory
+    #[clap(short, long)]
+    env: Option<String>,
+
     /// Output as JSON - JSON has the highest precedence and will override other output formats
     #[clap(long)]
     json: bool,
@@ -27,8 +38,18 @@ pub async fn command(args: Args) -> Result<()> {
     let config = get_config()?;
     let key = config.get_key_or_default(args.key)?;
     let project_id = Choice::try_project(args.project_id, &key.fingerprint).await?;
-    let mut kvpairs = SDK::get_variables_pruned(&project_id, &key.fingerprint).await?;
-    kvpairs.sort_by(|a, b| a.key.cmp(&b.key));
+    let environment = Choice::try_environment(args.env, &project_id)?;
+    let kvpairs = if config.online {
+        SDK::get_variables_pruned(&project_id, &key.fingerprint).await?
+    } else {
+        LocalStore::get_variables_pruned(&project_id)?
+    };
+    let kvpairs = select_environment(kvpairs, environment.as_deref());
+
+    let rotate = LocalProject::read(&project_id)?.rotate;
+    if !rotate.is_empty() {
+        eprintln!("{} {}", "Flagged for rotation:".yellow(), rotate.join(", "));
+    }
 
     match mode {
         Mode::KV => {
@@ -40,7 +61,11 @@ pub async fn command(args: Args) -> Result<()> {
         }
         Mode::Table => {
             let btreemap = kvpairs.to_btreemap()?;
-            Table::new("Variables".into(), btreemap).print()?;
+            let title = match &environment {
+                Some(env) => format!("Variables ({})", env),
+                None => "Variables".into(),
+            };
+            Table::new(title, btreemap).print()?;
         }
     }
 
@@ -1 +1,4 @@
 pub const MINIMUM_PASSWORD_LENGTH: usize = 8;
+
+/// The hosted server, used when `sdk_url` isn't set
+pub const DEFAULT_API_URL: &str = "https://api.env-cli.com";
@@ -0,0 +1,430 @@
+use crate::error::{Error, Result};
+use chrono::{DateTime, Duration, Utc};
+use pgp::composed::message::Message;
+use pgp::{
+    composed,
+    composed::signed_key::*,
+    crypto::{self, hash::HashAlgorithm, public_key::PublicKeyAlgorithm},
+    packet::{KeyFlags, Signature, SignatureType},
+    types::{
+        CompressionAlgorithm, KeyId, KeyTrait, Mpi, PublicKeyTrait, SecretKeyTrait, StringToKey,
+    },
+    Deserializable,
+};
+use rand::prelude::*;
+use smallvec::*;
+use std::io::Cursor;
+
+/// Algorithm of a new key
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum KeyAlgorithm {
+    #[default]
+    Rsa2048,
+    Rsa4096,
+    /// EdDSA signing key with a Curve25519 (ECDH) encryption subkey
+    Ed25519,
+}
+
+#[derive(Debug)]
+pub struct KeyPair {
+    pub secret_key: pgp::SignedSecretKey,
+    pub public_key: pgp::SignedPublicKey,
+}
+
+/// The public half of a secret key, reusing its signatures so no passphrase is needed
+pub fn signed_public_key(secret_key: &SignedSecretKey) -> SignedPublicKey {
+    let mut public_subkeys = secret_key.public_subkeys.clone();
+    public_subkeys.extend(
+        secret_key
+            .secret_subkeys
+            .iter()
+            .map(|k| SignedPublicSubKey::new(k.key.public_key(), k.signatures.clone())),
+    );
+
+    SignedPublicKey::new(
+        secret_key.primary_key.public_key(),
+        secret_key.details.clone(),
+        public_subkeys,
+    )
+}
+
+/// Generate a key pair protected by `password`
+///
+/// envx keys don't carry a name or email, their user ID is a salted hash of them
+pub fn generate_key_pair(
+    primary_user_id: String,
+    password: String,
+    algorithm: KeyAlgorithm,
+) -> Result<KeyPair> {
+    let mut key_params = composed::key::SecretKeyParamsBuilder::default();
+
+    key_params
+        .can_create_certificates(false)
+        .can_sign(true)
+        .passphrase(Some(password.clone()))
+        .primary_user_id(primary_user_id)
+        .preferred_symmetric_algorithms(smallvec![crypto::sym::SymmetricKeyAlgorithm::AES256]);
+
+    match algorithm {
+        KeyAlgorithm::Rsa2048 | KeyAlgorithm::Rsa4096 => {
+            let bits = if algorithm == KeyAlgorithm::Rsa4096 {
+                4096
+            } else {
+                2048
+            };
+            key_params
+                .key_type(composed::KeyType::Rsa(bits))
+                .can_encrypt(true);
+        }
+        // EdDSA keys can't encrypt, messages are encrypted to the subkey
+        KeyAlgorithm::Ed25519 => {
+            let subkey = composed::key::SubkeyParamsBuilder::default()
+                .key_type(composed::KeyType::ECDH)
+                .can_encrypt(true)
+                .passphrase(Some(password.clone()))
+                .build()
+                .map_err(|e| {
+                    Error::InvalidKey(format!("Failed to build the encryption subkey: {}", e))
+                })?;
+            key_params
+                .key_type(composed::KeyType::EdDSA)
+                .can_encrypt(false)
+                .subkey(subkey);
+        }
+    }
+
+    let secret_key_params = key_params
+        .build()
+        .map_err(|e| Error::InvalidKey(format!("Failed to build the key parameters: {}", e)))?;
+
+    let secret_key = secret_key_params
+        .generate()
+        .map_err(|e| Error::InvalidKey(format!("Failed to generate the key: {}", e)))?;
+
+    let passwd_fn = || password.clone();
+
+    let signed_secret_key = secret_key
+        .sign(passwd_fn)
+        .map_err(|e| Error::InvalidKey(format!("Failed to self-sign the secret key: {}", e)))?;
+
+    let public_key = signed_secret_key.public_key();
+    let signed_public_key = public_key
+        .sign(&signed_secret_key, passwd_fn)
+        .map_err(|e| Error::InvalidKey(format!("Failed to self-sign the public key: {}", e)))?;
+
+    let key_pair = KeyPair {
+        secret_key: signed_secret_key,
+        public_key: signed_public_key,
+    };
+
+    Ok(key_pair)
+}
+
+/// The key a message is encrypted to for one recipient
+///
+/// RSA keys generated by envx encrypt with their primary key, Ed25519 keys and most GnuPG
+/// keys with a subkey flagged for encryption.
+/// `encrypt_to_keys` takes a single key type, so mixed recipients go through this.
+#[derive(Debug)]
+enum EncryptionKey<'a> {
+    Primary(&'a SignedPublicKey),
+    Subkey(&'a SignedPublicSubKey),
+}
+
+impl<'a> EncryptionKey<'a> {
+    fn of(key: &'a SignedPublicKey) -> Result<Self> {
+        let can_encrypt = |f: &KeyFlags| f.encrypt_comms() || f.encrypt_storage();
+
+        if let Some(subkey) = key.public_subkeys.iter().find(|k| {
+            k.is_encryption_key()
+                && is_live(k, Utc::now())
+                && allows(&key_flags(&k.signatures), can_encrypt)
+        }) {
+            return Ok(Self::Subkey(subkey));
+        }
+        if key.is_encryption_key() && allows(&primary_key_flags(key), can_encrypt) {
+            return Ok(Self::Primary(key));
+        }
+
+        Err(Error::InvalidKey(format!(
+            "Key {} has no encryption key",
+            hex::encode(key.fingerprint())
+        )))
+    }
+}
+
+/// Whether a subkey is neither revoked nor expired
+///
+/// Signatures aren't checked here, `verify` does that when a key is imported
+fn is_live(subkey: &SignedPublicSubKey, now: DateTime<Utc>) -> bool {
+    if subkey
+        .signatures
+        .iter()
+        .any(|s| s.typ() == SignatureType::SubkeyRevocation)
+    {
+        return false;
+    }
+
+    // The newest binding decides. The expiration time is stored as seconds after the
+    // creation of the key, 0 meaning never
+    let expiration = subkey
+        .signatures
+        .iter()
+        .filter(|s| s.typ() == SignatureType::SubkeyBinding)
+        .max_by_key(|s| s.created())
+        .and_then(|s| s.key_expiration_time())
+        .map(|t| t.timestamp())
+        .filter(|&seconds| seconds > 0);
+    match expiration {
+        Some(seconds) => *subkey.key.created_at() + Duration::seconds(seconds) > now,
+        None => true,
+    }
+}
+
+fn key_flags<'a>(signatures: impl IntoIterator<Item = &'a Signature>) -> Vec<KeyFlags> {
+    signatures.into_iter().map(|s| s.key_flags()).collect()
+}
+
+/// Flags of the primary key, from its self-signatures
+fn primary_key_flags(key: &SignedPublicKey) -> Vec<KeyFlags> {
+    key_flags(
+        key.details
+            .users
+            .iter()
+            .flat_map(|u| &u.signatures)
+            .chain(&key.details.direct_signatures),
+    )
+}
+
+/// Keys without flags can do whatever their algorithm allows
+fn allows(flags: &[KeyFlags], usage: impl Fn(&KeyFlags) -> bool) -> bool {
+    flags.iter().all(|f| *f == KeyFlags::default()) || flags.iter().any(usage)
+}
+
+impl KeyTrait for EncryptionKey<'_> {
+    fn fingerprint(&self) -> Vec<u8> {
+        match self {
+            Self::Primary(k) => k.fingerprint(),
+            Self::Subkey(k) => k.fingerprint(),
+        }
+    }
+
+    fn key_id(&self) -> KeyId {
+        match self {
+            Self::Primary(k) => k.key_id(),
+            Self::Subkey(k) => k.key_id(),
+        }
+    }
+
+    fn algorithm(&self) -> PublicKeyAlgorithm {
+        match self {
+            Self::Primary(k) => k.algorithm(),
+            Self::Subkey(k) => k.algorithm(),
+        }
+    }
+}
+
+impl PublicKeyTrait for EncryptionKey<'_> {
+    fn verify_signature(
+        &self,
+        hash: HashAlgorithm,
+        data: &[u8],
+        sig: &[Mpi],
+    ) -> pgp::errors::Result<()> {
+        match self {
+            Self::Primary(k) => k.verify_signature(hash, data, sig),
+            Self::Subkey(k) => k.verify_signature(hash, data, sig),
+        }
+    }
+
+    fn encrypt<R: CryptoRng + Rng>(
+        &self,
+        rng: &mut R,
+        plain: &[u8],
+    ) -> pgp::errors::Result<Vec<Mpi>> {
+        match self {
+            Self::Primary(k) => k.encrypt(rng, plain),
+            Self::Subkey(k) => k.encrypt(rng, plain),
+        }
+    }
+
+    fn to_writer_old(&self, writer: &mut impl std::io::Write) -> pgp::errors::Result<()> {
+        match self {
+            Self::Primary(k) => k.to_writer_old(writer),
+            Self::Subkey(k) => k.to_writer_old(writer),
+        }
+    }
+}
+
+/// Fail unless the primary key can sign auth tokens and the key can be encrypted to
+pub fn ensure_usable(key: &SignedPublicKey) -> Result<()> {
+    if !key.is_signing_key() || !allows(&primary_key_flags(key), KeyFlags::sign) {
+        return Err(Error::InvalidKey(format!(
+            "The primary key of {} can't sign",
+            hex::encode(key.fingerprint())
+        )));
+    }
+    EncryptionKey::of(key)?;
+    Ok(())
+}
+
+pub fn encrypt(msg: &str, pubkey_str: &str) -> Result<String> {
+    let (pubkey, _) = SignedPublicKey::from_string(pubkey_str)
+        .map_err(|e| Error::InvalidKey(format!("Failed to parse the public key: {}", e)))?;
+
+    encrypt_multi(msg, &[pubkey])
+}
+
+pub fn encrypt_multi(msg: &str, pubkeys: &[SignedPublicKey]) -> Result<String> {
+    let mut rng = StdRng::from_entropy();
+
+    let encryption_keys = pubkeys
+        .iter()
+        .map(EncryptionKey::of)
+        .collect::<Result<SmallVec<[EncryptionKey; 1]>>>()?;
+    let borrowed_keys = encryption_keys
+        .iter()
+        .collect::<SmallVec<[&EncryptionKey; 1]>>();
+
+    // Requires a file name as the first arg, in this case I pass "none", as it's not used
+    let msg = composed::message::Message::new_literal("none", msg);
+
+    msg.encrypt_to_keys(
+        &mut rng,
+        crypto::sym::SymmetricKeyAlgorithm::AES128,
+        &borrowed_keys,
+    )
+    .and_then(|m| m.to_armored_string(None))
+    .map_err(|e| Error::Encryption(e.to_string()))
+}
+
+pub fn decrypt(armored: &str, seckey: &SignedSecretKey, password: String) -> Result<String> {
+    let decryption_error =
+        |message: &str, e: pgp::errors::Error| Error::Decryption(format!("{}: {}", message, e));
+
+    let buf = Cursor::new(armored);
+    let (msg, _) = composed::message::Message::from_armor_single(buf)
+        .map_err(|e| decryption_error("Failed to parse the armored message", e))?;
+    let (mut decryptor, _) = msg
+        .decrypt(|| password, &[seckey])
+        .map_err(|e| decryption_error("Failed to decrypt the message", e))?;
+
+    let msg = decryptor
+        .next()
+        .ok_or_else(|| Error::Decryption("Failed to find message".to_string()))?
+        .map_err(|e| decryption_error("Failed to decrypt the message", e))?;
+    content(msg)
+}
+
+/// Compress and encrypt with a passphrase instead of keys, e.g. for backups
+pub fn encrypt_with_passphrase(msg: &str, passphrase: String) -> Result<String> {
+    let mut rng = StdRng::from_entropy();
+    let s2k = StringToKey::new_default(&mut rng);
+
+    Message::new_literal("none", msg)
+        .compress(CompressionAlgorithm::ZLIB)
+        .and_then(|m| {
+            m.encrypt_with_password(
+                &mut rng,
+                s2k,
+                crypto::sym::SymmetricKeyAlgorithm::AES256,
+                || passphrase,
+            )
+        })
+        .and_then(|m| m.to_armored_string(None))
+        .map_err(|e| Error::Encryption(e.to_string()))
+}
+
+pub fn decrypt_with_passphrase(armored: &str, passphrase: String) -> Result<String> {
+    let (msg, _) = Message::from_string(armored)
+        .map_err(|e| Error::Decryption(format!("Failed to parse the armored message: {}", e)))?;
+
+    let msg = msg
+        .decrypt_with_password(|| passphrase)
+        .ok()
+        .and_then(|mut decryptor| decryptor.next())
+        .and_then(|m| m.ok())
+        .ok_or_else(|| Error::Decryption("Wrong passphrase".to_string()))?;
+    content(msg)
+}
+
+/// The text of a decrypted message
+fn content(msg: Message) -> Result<String> {
+    let bytes = msg
+        .get_content()
+        .map_err(|e| Error::Decryption(format!("Failed to get content: {}", e)))?
+        .ok_or_else(|| Error::Decryption("Failed to get content".to_string()))?;
+
+    String::from_utf8(bytes).map_err(|e| Error::Decryption(format!("Invalid UTF-8: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pgp::packet::{Subpacket, SubpacketData};
+    use std::sync::OnceLock;
+
+    fn key_pair(algorithm: KeyAlgorithm) -> KeyPair {
+        generate_key_pair("test".to_string(), "password".to_string(), algorithm).unwrap()
+    }
+
+    /// Generating a key takes seconds in debug builds, so the tests share this one
+    fn ed25519() -> &'static KeyPair {
+        static KEY: OnceLock<KeyPair> = OnceLock::new();
+        KEY.get_or_init(|| key_pair(KeyAlgorithm::Ed25519))
+    }
+
+    #[test]
+    fn encrypts_to_rsa_and_ed25519_keys_at_once() {
+        let rsa = key_pair(KeyAlgorithm::Rsa2048);
+        let ed25519 = ed25519();
+
+        let armored = encrypt_multi(
+            "secret",
+            &[rsa.public_key.clone(), ed25519.public_key.clone()],
+        )
+        .unwrap();
+
+        for key in [&rsa, ed25519] {
+            let decrypted = decrypt(&armored, &key.secret_key, "password".to_string()).unwrap();
+            assert_eq!(decrypted, "secret");
+        }
+    }
+
+    #[test]
+    fn encrypts_to_the_subkey_of_a_sign_only_primary() {
+        let key = &ed25519().public_key;
+        assert!(!key.is_encryption_key());
+
+        match EncryptionKey::of(key).unwrap() {
+            EncryptionKey::Subkey(subkey) => assert_eq!(subkey, &key.public_subkeys[0]),
+            EncryptionKey::Primary(_) => panic!("encrypted to the signing key"),
+        }
+        ensure_usable(key).unwrap();
+    }
+
+    #[test]
+    fn skips_revoked_and_expired_subkeys() {
+        let key = &ed25519().public_key;
+        let mut revoked = key.clone();
+        let mut revocation = key.public_subkeys[0].signatures[0].clone();
+        revocation.config.typ = SignatureType::SubkeyRevocation;
+        revoked.public_subkeys[0].signatures.push(revocation);
+        assert!(EncryptionKey::of(&revoked).is_err());
+
+        // Expires an hour after it was created
+        let mut expiring = key.public_subkeys[0].clone();
+        expiring.signatures[0]
+            .config
+            .hashed_subpackets
+            .push(Subpacket::regular(SubpacketData::KeyExpirationTime(
+                DateTime::from_timestamp(3600, 0).unwrap(),
+            )));
+        assert!(is_live(&expiring, Utc::now()));
+        assert!(!is_live(&expiring, Utc::now() + Duration::hours(2)));
+        assert!(is_live(
+            &key.public_subkeys[0],
+            Utc::now() + Duration::days(365)
+        ));
+    }
+}
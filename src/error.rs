@@ -0,0 +1,64 @@
+use thiserror::Error;
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Errors returned by the library, e.g. by [`crate::client::Client`]
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The server rejected the token, or the key couldn't sign one
+    #[error("Unauthorized: {0}")]
+    Unauthorized(String),
+
+    /// The project, user or variable doesn't exist, or isn't visible to this key
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    /// A value couldn't be encrypted to the members of a project
+    #[error("Encryption failed: {0}")]
+    Encryption(String),
+
+    /// A value couldn't be decrypted with the keys at hand
+    #[error("Decryption failed: {0}")]
+    Decryption(String),
+
+    /// A secret key, or the public key of a project member, couldn't be parsed
+    #[error("Invalid key: {0}")]
+    InvalidKey(String),
+
+    /// The server couldn't be reached, or answered with something unexpected
+    #[error("Network error: {0}")]
+    Network(String),
+
+    /// Invalid URL or passphrase source
+    #[error("Configuration error: {0}")]
+    Config(String),
+
+    /// A variable, timestamp or data file that isn't in the expected format
+    #[error("Parse error: {0}")]
+    Parse(String),
+
+    /// A file couldn't be read or written
+    #[error("I/O error: {0}")]
+    Io(String),
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        let url = e.url().map(|u| u.to_string()).unwrap_or_default();
+        let message = if e.is_timeout() {
+            format!("Request to {} timed out", url)
+        } else if e.is_connect() {
+            format!("Couldn't connect to {}, check your connection", url)
+        } else if e.is_body() || e.is_decode() {
+            format!("Connection dropped while reading the response from {}", url)
+        } else {
+            e.to_string()
+        };
+
+        // The root cause is the useful part, e.g. "connection refused"
+        match std::error::Error::source(&e) {
+            Some(source) => Self::Network(format!("{}: {}", message, source)),
+            None => Self::Network(message),
+        }
+    }
+}
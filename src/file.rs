@@ -0,0 +1,57 @@
+use crate::error::{Error, Result};
+use std::{fs, io::Write, path::Path};
+
+/// Write a file that only the current user can read (0600 on unix)
+pub fn write_private(path: &Path, contents: &str) -> Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+
+        // `mode` only applies to new files
+        if path.exists() {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600)).map_err(|e| {
+                Error::Io(format!(
+                    "Failed to set the permissions of {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+        }
+    }
+
+    let mut file = options
+        .open(path)
+        .map_err(|e| Error::Io(format!("Failed to open {}: {}", path.display(), e)))?;
+    file.write_all(contents.as_bytes())
+        .map_err(|e| Error::Io(format!("Failed to write {}: {}", path.display(), e)))?;
+
+    Ok(())
+}
+
+/// Replace a file with a temp file and a rename, so readers never see a partial write
+///
+/// The new file is only readable by the current user
+pub fn write_atomic(path: &Path, contents: &str) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| Error::Io(format!("{} is not a file", path.display())))?
+        .to_string_lossy();
+    let tmp = path.with_file_name(format!(".{}.{}.tmp", file_name, std::process::id()));
+
+    write_private(&tmp, contents)?;
+    let synced = fs::File::open(&tmp).and_then(|f| f.sync_all());
+    if let Err(e) = synced.and_then(|_| fs::rename(&tmp, path)) {
+        let _ = fs::remove_file(&tmp);
+        return Err(Error::Io(format!(
+            "Failed to replace {}: {}",
+            path.display(),
+            e
+        )));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,201 @@
+use crate::error::{Error, Result};
+use rand::Rng;
+use reqwest::{header, Method, RequestBuilder, Response, StatusCode};
+use std::time::Duration;
+
+/// Longest we wait between two attempts, whatever `Retry-After` says
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+const BASE_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Timeouts and retry policy of an [`Http`]
+#[derive(Clone, Debug)]
+pub struct HttpOptions {
+    pub connect_timeout: Duration,
+    /// For the whole request, including reading the body
+    pub request_timeout: Duration,
+    /// Retries after the first attempt, 0 disables them
+    pub max_retries: u32,
+}
+
+impl Default for HttpOptions {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(10),
+            request_timeout: Duration::from_secs(30),
+            max_retries: 3,
+        }
+    }
+}
+
+/// Connection pool and retry policy
+///
+/// Cheap to clone, clones share their connections
+#[derive(Clone, Debug)]
+pub struct Http {
+    inner: reqwest::Client,
+    max_retries: u32,
+}
+
+impl Http {
+    pub fn new(options: &HttpOptions) -> Result<Self> {
+        let inner = reqwest::Client::builder()
+            .connect_timeout(options.connect_timeout)
+            .timeout(options.request_timeout)
+            .build()
+            .map_err(|e| Error::Config(format!("Failed to build the HTTP client: {}", e)))?;
+
+        Ok(Self {
+            inner,
+            max_retries: options.max_retries,
+        })
+    }
+
+    pub fn request(&self, method: Method, url: url::Url) -> RequestBuilder {
+        self.inner.request(method, url)
+    }
+
+    /// Send a request, retrying it if that's safe
+    ///
+    /// Requests the server never processed (connection failures, 429) are always retried.
+    /// Idempotent ones are also retried on timeouts and 5xx responses. Attempts are spaced
+    /// with exponential backoff, or whatever delay the server asks for in `Retry-After`.
+    /// Non-2xx responses become errors carrying the server's message.
+    pub async fn send(&self, request: RequestBuilder) -> Result<Response> {
+        let mut attempt = 0;
+        loop {
+            // Bodies are always in memory, so this only fails for streams
+            let Some(next) = request.try_clone() else {
+                return check(request.send().await?).await;
+            };
+            let (client, built) = next.build_split();
+            let built = built?;
+            let idempotent = is_idempotent(built.method());
+            let can_retry = attempt < self.max_retries;
+
+            let delay = match client.execute(built).await {
+                Ok(res) if can_retry && is_transient(res.status(), idempotent) => {
+                    retry_after(&res).unwrap_or_else(|| backoff(attempt))
+                }
+                Ok(res) => return check(res).await,
+                Err(e) if can_retry && (e.is_connect() || (idempotent && e.is_timeout())) => {
+                    backoff(attempt)
+                }
+                Err(e) => return Err(e.into()),
+            };
+
+            tokio::time::sleep(delay.min(MAX_BACKOFF)).await;
+            attempt += 1;
+        }
+    }
+}
+
+/// The default timeouts and retries of [`HttpOptions`]
+impl Default for Http {
+    fn default() -> Self {
+        // Only fails if the TLS backend can't be initialized, where `reqwest::Client::new`
+        // panics as well
+        Self::new(&HttpOptions::default()).expect("Failed to build the HTTP client")
+    }
+}
+
+/// Turn non-2xx responses into errors carrying the server's message
+pub async fn check(res: Response) -> Result<Response> {
+    let status = res.status();
+    if status.is_success() {
+        return Ok(res);
+    }
+
+    let message = match res.text().await {
+        Ok(text) if !text.trim().is_empty() => text,
+        _ => status.to_string(),
+    };
+
+    Err(match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Error::Unauthorized(message),
+        StatusCode::NOT_FOUND => Error::NotFound(message),
+        _ => Error::Network(format!("Server responded with {}: {}", status, message)),
+    })
+}
+
+fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::PUT | Method::DELETE | Method::OPTIONS
+    )
+}
+
+fn is_transient(status: StatusCode, idempotent: bool) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || (idempotent && status.is_server_error())
+}
+
+/// 0.5s, 1s, 2s... with up to 25% of jitter so clients don't retry in lockstep
+fn backoff(attempt: u32) -> Duration {
+    let delay = BASE_BACKOFF.saturating_mul(2u32.saturating_pow(attempt));
+    let jitter = rand::thread_rng().gen_range(0.0..0.25);
+    delay.mul_f64(1.0 + jitter)
+}
+
+/// `Retry-After` is either a number of seconds or an HTTP date
+fn retry_after(res: &Response) -> Option<Duration> {
+    let value = res
+        .headers()
+        .get(header::RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = chrono::DateTime::parse_from_rfc2822(value).ok()?;
+    (date.with_timezone(&chrono::Utc) - chrono::Utc::now())
+        .to_std()
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_doubles_with_up_to_a_quarter_of_jitter() {
+        for (attempt, base) in [(0, 500), (1, 1000), (2, 2000), (3, 4000)] {
+            let base = Duration::from_millis(base);
+            for _ in 0..20 {
+                let delay = backoff(attempt);
+                assert!(delay >= base, "{:?} < {:?}", delay, base);
+                assert!(
+                    delay < base.mul_f64(1.25),
+                    "{:?} >= 1.25 * {:?}",
+                    delay,
+                    base
+                );
+            }
+        }
+
+        // Saturates instead of overflowing, `send` caps it at MAX_BACKOFF
+        assert!(backoff(u32::MAX) >= MAX_BACKOFF);
+    }
+
+    #[test]
+    fn retries_server_errors_of_idempotent_requests_only() {
+        for idempotent in [true, false] {
+            assert!(is_transient(StatusCode::TOO_MANY_REQUESTS, idempotent));
+            assert!(!is_transient(StatusCode::OK, idempotent));
+            assert!(!is_transient(StatusCode::BAD_REQUEST, idempotent));
+            assert!(!is_transient(StatusCode::UNAUTHORIZED, idempotent));
+            assert!(!is_transient(StatusCode::NOT_FOUND, idempotent));
+        }
+
+        assert!(is_transient(StatusCode::INTERNAL_SERVER_ERROR, true));
+        assert!(is_transient(StatusCode::SERVICE_UNAVAILABLE, true));
+        assert!(!is_transient(StatusCode::INTERNAL_SERVER_ERROR, false));
+        assert!(!is_transient(StatusCode::BAD_GATEWAY, false));
+
+        assert!(is_idempotent(&Method::GET));
+        assert!(is_idempotent(&Method::DELETE));
+        assert!(!is_idempotent(&Method::POST));
+        assert!(!is_idempotent(&Method::PATCH));
+    }
+}
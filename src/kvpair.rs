@@ -0,0 +1,99 @@
+use crate::error::{Error, Result};
+use crypto_hash::{hex_digest, Algorithm};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct KVPair {
+    pub key: String,
+    pub value: String,
+    /// Environment the variable belongs to, shared by all environments if None
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<String>,
+}
+
+impl KVPair {
+    pub fn new(key: String, value: String) -> Self {
+        Self {
+            key,
+            value,
+            environment: None,
+        }
+    }
+
+    pub fn with_environment(mut self, environment: Option<String>) -> Self {
+        self.environment = environment;
+        self
+    }
+
+    /// The key, qualified with the environment
+    ///
+    /// Versions of a variable share the same scoped key
+    pub fn scoped_key(&self) -> String {
+        match &self.environment {
+            Some(env) => format!("{}:{}", env, self.key),
+            None => self.key.clone(),
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self> {
+        serde_json::from_str::<KVPair>(json)
+            .map_err(|e| Error::Parse(format!("Failed to parse KVPair: {}", e)))
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string(&self)
+            .map_err(|e| Error::Parse(format!("Failed to serialize KVPair: {}", e)))
+    }
+}
+
+/// Hide a value, the short checksum still tells different values apart
+pub fn mask(value: &str) -> String {
+    let checksum = hex_digest(Algorithm::SHA256, value.as_bytes());
+    format!("******** ({})", &checksum[..8])
+}
+
+/// Variables visible in an environment
+///
+/// Shared variables, overridden by the ones specific to the environment
+pub fn select_environment(kvpairs: Vec<KVPair>, environment: Option<&str>) -> Vec<KVPair> {
+    let mut selected = BTreeMap::<String, KVPair>::new();
+
+    for kvpair in kvpairs.iter().filter(|k| k.environment.is_none()) {
+        selected.insert(kvpair.key.clone(), kvpair.clone());
+    }
+    if let Some(environment) = environment {
+        for kvpair in kvpairs
+            .iter()
+            .filter(|k| k.environment.as_deref() == Some(environment))
+        {
+            selected.insert(kvpair.key.clone(), kvpair.clone());
+        }
+    }
+
+    selected.into_values().collect()
+}
+
+impl fmt::Display for KVPair {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}={}", self.key, self.value)
+    }
+}
+
+impl FromStr for KVPair {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let split = &s.splitn(2, '=').collect::<Vec<&str>>();
+        if split.len() != 2 {
+            return Err(Error::Parse("Invalid key=value pair".to_string()));
+        }
+
+        let key = split[0].to_uppercase().to_string();
+        let value = split[1].to_string();
+
+        Ok(Self::new(key, value))
+    }
+}
@@ -0,0 +1,35 @@
+//! Client for envx servers
+//!
+//! Fetch the secrets of a project at startup, the same way `envx run` does:
+//!
+//! ```no_run
+//! use envx::client::{Client, Identity, Passphrase};
+//!
+//! # async fn run() -> Result<(), envx::error::Error> {
+//! let identity = Identity::new("<user id>", &std::fs::read_to_string("private.key").unwrap())?;
+//! let client = Client::new(
+//!     "https://api.env-cli.com",
+//!     identity,
+//!     Passphrase::Env("ENVX_PASSPHRASE".into()),
+//! )?;
+//!
+//! for kvpair in client.variables_pruned("<project id>").await? {
+//!     std::env::set_var(kvpair.key, kvpair.value);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod client;
+pub mod crypto;
+pub mod error;
+pub mod file;
+pub mod http;
+pub mod kvpair;
+pub mod partial_variable;
+pub mod paths;
+pub mod token_cache;
+pub mod types;
+
+#[cfg(feature = "server")]
+pub mod server;
@@ -5,7 +5,6 @@ use commands::*;
 mod commands;
 mod constants;
 mod sdk;
-mod types;
 mod utils;
 
 #[macro_use]
@@ -21,6 +20,10 @@ pub struct Args {
 
     #[clap(long)]
     silent: bool,
+
+    /// Server profile to use, defaults to $ENVX_PROFILE
+    #[clap(long, global = true)]
+    profile: Option<String>,
 }
 
 // Generates the commands based on the modules in the commands directory
@@ -28,17 +31,29 @@ pub struct Args {
 commands_enum!(
     add_user_to_project,
     auth,
+    backup,
     debug,
     decrypt,
+    diff,
     encrypt,
+    environments,
     export,
     gen,
+    history,
     import,
     link,
+    prune,
+    pull,
+    push,
+    remove_user_from_project,
+    render,
+    restore,
+    rollback,
     run,
     set,
     shell,
     sign,
+    sync,
     unlink,
     unset,
     upload,
@@ -48,6 +63,7 @@ commands_enum!(
     config,
     delete,
     get,
+    key,
     keyring,
     new
 );
@@ -55,6 +71,9 @@ commands_enum!(
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Args::parse();
+    if let Some(profile) = cli.profile.clone() {
+        utils::resolve::set_profile(profile);
+    }
 
     match Commands::exec(cli).await {
         Ok(_) => {}
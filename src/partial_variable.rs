@@ -1,6 +1,8 @@
+use crate::error::{Error, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-use super::kvpair::KVPair;
+use crate::kvpair::KVPair;
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct PartialVariable {
     pub id: String,
@@ -10,6 +12,7 @@ pub struct PartialVariable {
 }
 
 impl PartialVariable {
+    #[allow(dead_code)]
     pub fn to_parsed(&self) -> ParsedPartialVariable {
         ParsedPartialVariable {
             id: self.id.clone(),
@@ -30,6 +33,7 @@ impl PartialVariable {
 }
 
 pub trait ToParsed {
+    #[allow(dead_code)]
     fn to_parsed(&self) -> Vec<ParsedPartialVariable>;
     fn zip_to_parsed(&self, kvpair: Vec<KVPair>) -> Vec<ParsedPartialVariable>;
 }
@@ -55,6 +59,41 @@ pub struct ParsedPartialVariable {
     pub created_at: String,
 }
 
+impl ParsedPartialVariable {
+    pub fn created_at(&self) -> Result<DateTime<Utc>> {
+        parse_timestamp(&self.created_at)
+    }
+}
+
+/// Parse a `created_at` timestamp
+///
+/// Accepts RFC 3339 (local store) as well as naive timestamps, which are assumed to be UTC
+pub fn parse_timestamp(timestamp: &str) -> Result<DateTime<Utc>> {
+    if let Ok(t) = DateTime::parse_from_rfc3339(timestamp) {
+        return Ok(t.with_timezone(&Utc));
+    }
+
+    for format in [
+        "%Y-%m-%dT%H:%M:%S%.f",
+        "%Y-%m-%d %H:%M:%S%.f",
+        "%Y-%m-%dT%H:%M",
+    ] {
+        if let Ok(t) = NaiveDateTime::parse_from_str(timestamp, format) {
+            return Ok(t.and_utc());
+        }
+    }
+
+    Err(Error::Parse(format!("Invalid timestamp: {}", timestamp)))
+}
+
+/// Ordering for `sort_by`, newest `created_at` first
+pub fn newest_first(a: &ParsedPartialVariable, b: &ParsedPartialVariable) -> std::cmp::Ordering {
+    match (a.created_at(), b.created_at()) {
+        (Ok(a), Ok(b)) => b.cmp(&a),
+        _ => b.created_at.cmp(&a.created_at),
+    }
+}
+
 pub trait DeDupe {
     fn dedupe(&self) -> Self;
 }
@@ -65,14 +104,14 @@ impl DeDupe for Vec<ParsedPartialVariable> {
     fn dedupe(&self) -> Self {
         // Sort the vector based on the `created_at` timestamp in descending order
         let mut sorted_vec = self.clone();
-        sorted_vec.sort_by(|a, b| b.created_at.cmp(&a.created_at));
+        sorted_vec.sort_by(newest_first);
 
         // HashMap to track encountered keys
         let mut seen: HashMap<String, ParsedPartialVariable> = HashMap::new();
 
         // Iterate and populate the HashMap
         for variable in sorted_vec {
-            let key = variable.value.key.clone();
+            let key = variable.value.scoped_key();
             seen.entry(key).or_insert(variable);
         }
 
@@ -93,10 +132,16 @@ impl ToKVPair for Vec<ParsedPartialVariable> {
 
 impl Display for ParsedPartialVariable {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&format!(
-            "{} - ({}) - {}",
-            self.id, self.value, self.project_id
-        ))?;
+        match &self.value.environment {
+            Some(env) => f.write_str(&format!(
+                "{} - ({}) [{}] - {}",
+                self.id, self.value, env, self.project_id
+            ))?,
+            None => f.write_str(&format!(
+                "{} - ({}) - {}",
+                self.id, self.value, self.project_id
+            ))?,
+        }
         Ok(())
     }
 }
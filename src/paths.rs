@@ -0,0 +1,131 @@
+use crate::error::{Error, Result};
+use std::path::{Path, PathBuf};
+
+pub const ENV_CONFIG_DIR: &str = "ENVX_CONFIG_DIR";
+
+/// Directory the envx CLI keeps its config, keys and offline store in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigDir {
+    /// `ENVX_CONFIG_DIR`
+    Env(PathBuf),
+    /// `$XDG_CONFIG_HOME/envcli`
+    Xdg(PathBuf),
+    /// ~/.config/envcli
+    Default(PathBuf),
+    /// ~/.config/envcli, still used while `XDG_CONFIG_HOME` is set because `xdg` doesn't
+    /// exist yet. `envx config migrate` moves it there
+    Legacy { path: PathBuf, xdg: PathBuf },
+}
+
+impl ConfigDir {
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Env(path) | Self::Xdg(path) | Self::Default(path) => path,
+            Self::Legacy { path, .. } => path,
+        }
+    }
+}
+
+/// `ENVX_CONFIG_DIR`, then `$XDG_CONFIG_HOME/envcli`, then ~/.config/envcli
+///
+/// Never moves anything, an existing ~/.config/envcli stays in use until it is migrated
+pub fn config_dir() -> Result<ConfigDir> {
+    let var = |name: &str| std::env::var(name).ok().filter(|v| !v.trim().is_empty());
+
+    lookup(
+        var(ENV_CONFIG_DIR),
+        var("XDG_CONFIG_HOME"),
+        home::home_dir(),
+    )
+}
+
+fn lookup(
+    config_dir: Option<String>,
+    xdg_config_home: Option<String>,
+    home: Option<PathBuf>,
+) -> Result<ConfigDir> {
+    if let Some(dir) = config_dir {
+        return Ok(ConfigDir::Env(dir.into()));
+    }
+
+    let legacy = home
+        .ok_or_else(|| Error::Config("Failed to get home directory".to_string()))?
+        .join(".config")
+        .join("envcli");
+
+    Ok(match xdg_config_home {
+        Some(dir) => {
+            let xdg = PathBuf::from(dir).join("envcli");
+            if xdg.exists() || !legacy.exists() || xdg == legacy {
+                ConfigDir::Xdg(xdg)
+            } else {
+                ConfigDir::Legacy { path: legacy, xdg }
+            }
+        }
+        None => ConfigDir::Default(legacy),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn temp_home(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "envx-paths-{}-{}-{}",
+            name,
+            std::process::id(),
+            rand::random::<u32>()
+        ))
+    }
+
+    #[test]
+    fn env_wins_over_xdg_and_home() {
+        let home = temp_home("env");
+        let xdg = home.join("xdg").to_string_lossy().to_string();
+
+        let dir = lookup(Some("/envx".into()), Some(xdg.clone()), Some(home.clone())).unwrap();
+        assert_eq!(dir, ConfigDir::Env("/envx".into()));
+
+        // No home directory is needed when the variable is set
+        let dir = lookup(Some("/envx".into()), None, None).unwrap();
+        assert_eq!(dir.path(), Path::new("/envx"));
+
+        let dir = lookup(None, Some(xdg), Some(home.clone())).unwrap();
+        assert_eq!(dir, ConfigDir::Xdg(home.join("xdg").join("envcli")));
+
+        let dir = lookup(None, None, Some(home.clone())).unwrap();
+        assert_eq!(dir, ConfigDir::Default(home.join(".config").join("envcli")));
+
+        assert!(lookup(None, None, None).is_err());
+    }
+
+    #[test]
+    fn keeps_using_the_legacy_directory() {
+        let home = temp_home("legacy");
+        let legacy = home.join(".config").join("envcli");
+        let xdg = home.join("xdg");
+        fs::create_dir_all(&legacy).unwrap();
+
+        let dir = lookup(None, Some(xdg.to_string_lossy().into()), Some(home.clone())).unwrap();
+        assert_eq!(
+            dir,
+            ConfigDir::Legacy {
+                path: legacy.clone(),
+                xdg: xdg.join("envcli")
+            }
+        );
+        assert_eq!(dir.path(), legacy);
+        // Resolving the path doesn't move anything
+        assert!(legacy.exists());
+        assert!(!xdg.exists());
+
+        // Once migrated, the XDG directory wins
+        fs::create_dir_all(xdg.join("envcli")).unwrap();
+        let dir = lookup(None, Some(xdg.to_string_lossy().into()), Some(home.clone())).unwrap();
+        assert_eq!(dir, ConfigDir::Xdg(xdg.join("envcli")));
+
+        let _ = fs::remove_dir_all(home);
+    }
+}
@@ -1,22 +1,30 @@
-use super::*;
-use crate::{
+use crate::utils::{
+    config::{get_config, get_token_cache_path},
+    keyring::try_get_password,
+    resolve::{self, active_profile},
+    rpgp::decrypt_full_many,
+};
+use anyhow::{bail, Context, Result};
+use envx::{
+    client::{Client, Identity, Passphrase},
+    crypto::encrypt_multi,
+    error::Error,
+    http::Http,
+    kvpair::KVPair,
+    partial_variable::{DeDupe, ParsedPartialVariable, PartialVariable, ToKVPair, ToParsed},
+    token_cache::TokenCache,
     types::ProjectInfo,
-    utils::{
-        auth::get_token,
-        config::get_config,
-        kvpair::KVPair,
-        partial_variable::{ParsedPartialVariable, PartialVariable, ToKVPair, ToParsed},
-        rpgp::{decrypt_full_many, encrypt_multi},
-    },
 };
-use anyhow::bail;
 use pgp::{Deserializable, SignedPublicKey};
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-use reqwest::header;
 use serde::{Deserialize, Serialize};
-use serde_json::json;
+use std::{
+    collections::HashSet,
+    sync::{Arc, OnceLock},
+};
 use url::Url;
 
+#[allow(dead_code)]
 #[derive(Serialize, Deserialize, Debug)]
 pub struct SetEnvParams {
     pub message: String,
@@ -24,80 +32,87 @@ pub struct SetEnvParams {
     pub project_id: Option<String>,
 }
 
-pub fn get_api_url() -> Url {
-    fn try_get_url() -> Result<Url> {
-        let dev_mode = std::env::var("DEV_MODE").is_ok();
-        if dev_mode {
-            return Ok(Url::parse("http://localhost:3000")?);
-        }
-        let url = get_config()?
-            .sdk_url
-            .unwrap_or("https://api.env-cli.com".into());
-        let url = Url::parse(&url)?;
-        Ok(url)
+/// URL of the server, see `resolve::api_url`
+pub fn get_api_url() -> Result<Url> {
+    let url = resolve::api_url(&get_config()?)?;
+    Url::parse(&url.value)
+        .with_context(|| format!("Invalid server URL {} ({})", url.value, url.source))
+}
+
+/// Connection pool shared by every request of the process, configured by the settings
+fn http() -> Result<Http> {
+    static HTTP: OnceLock<Http> = OnceLock::new();
+    if let Some(http) = HTTP.get() {
+        return Ok(http.clone());
+    }
+
+    let http = Http::new(&get_config()?.get_settings()?.http_options())?;
+    Ok(HTTP.get_or_init(|| http).clone())
+}
+
+/// Tokens shared by every client of the process, and by later runs if `cache_tokens` is set
+pub fn token_cache() -> Result<TokenCache> {
+    static TOKENS: OnceLock<TokenCache> = OnceLock::new();
+    if let Some(tokens) = TOKENS.get() {
+        return Ok(tokens.clone());
     }
-    match try_get_url() {
-        Ok(u) => u,
-        Err(_) => Url::parse("http://localhost:3000")
-            .context("Failed to parse URL, this should literally never happen")
-            .unwrap(),
+
+    let mut tokens = TokenCache::default();
+    if get_config()?.get_settings()?.cache_tokens {
+        tokens = tokens.persist_to(get_token_cache_path()?);
     }
+    Ok(TOKENS.get_or_init(|| tokens).clone())
 }
 
+/// `Client` for a key of the envx config
+///
+/// Variables are decrypted with whichever configured key they were encrypted to,
+/// the passphrase comes from the keyring or a prompt.
 #[allow(clippy::upper_case_acronyms)]
-pub(crate) struct SDK {}
+pub struct SDK {}
 impl SDK {
-    async fn auth_header(partial_fingerprint: &str) -> Result<String> {
+    pub fn client(partial_fingerprint: &str) -> Result<Client> {
         let config = get_config()?;
         let key = config.get_key(partial_fingerprint)?;
-        let Some(uuid) = key.uuid else {
-            bail!("No UUID for key {}\nTry envx upload", partial_fingerprint)
+        let Some(uuid) = config.key_uuid(&key)? else {
+            bail!(
+                "No UUID for key {} on profile {}\nTry envx upload",
+                partial_fingerprint,
+                active_profile()
+            )
         };
-        let auth_token = get_token(&key.fingerprint, &uuid).await?;
-        Ok(format!("Bearer {}", auth_token))
+
+        let identity = Identity::new(uuid, &key.secret_key()?)?;
+        let fingerprint = key.fingerprint.clone();
+        let passphrase = Passphrase::Callback(Arc::new(move |_| {
+            get_config()
+                .and_then(|config| try_get_password(&fingerprint, &config))
+                .map_err(|e| Error::Config(format!("{:#}", e)))
+        }));
+
+        Ok(Client::new(get_api_url()?.as_str(), identity, passphrase)?
+            .with_http(http()?)
+            .with_token_cache(token_cache()?))
     }
 
     pub async fn new_user(username: &str, public_key: &str) -> Result<String> {
-        let client = reqwest::Client::new();
-
-        let body = json!({
-            "username": username,
-            "public_key": public_key
-        });
-
-        let url = get_api_url().join("/user/new")?;
-        let res = client.post(url).json(&body).send().await;
-
-        let res = match res {
-            Ok(r) => r.text().await?,
-            Err(e) => bail!("Failed to create new user: {}", e.to_string()),
-        };
+        Client::new_user(&http()?, get_api_url()?.as_str(), username, public_key)
+            .await
+            .context("Failed to create new user")
+    }
 
-        Ok(res)
+    pub async fn test_auth(partial_fingerprint: &str) -> Result<String> {
+        Ok(Self::client(partial_fingerprint)?.test_auth().await?)
     }
 
     pub async fn get_project_info(
         project_id: &str,
         partial_fingerprint: &str,
     ) -> Result<ProjectInfo> {
-        let client = reqwest::Client::new();
-
-        let url = get_api_url().join("project/")?.join(project_id)?;
-
-        let project_info = client
-            .get(url)
-            .header(
-                header::AUTHORIZATION,
-                Self::auth_header(partial_fingerprint).await?,
-            )
-            .send()
+        Self::client(partial_fingerprint)?
+            .project_info(project_id)
             .await
-            .context("Failed to get project info")?
-            .json::<ProjectInfo>()
-            .await
-            .context("Failed to parse project info")?;
-
-        Ok(project_info)
+            .context("Failed to get project info")
     }
 
     pub async fn set_many(
@@ -105,163 +120,57 @@ impl SDK {
         partial_fingerprint: &str,
         project_id: &str,
     ) -> Result<Vec<String>> {
-        let client = reqwest::Client::new();
-
-        let project_info = Self::get_project_info(project_id, partial_fingerprint).await?;
-
-        let recipients = project_info
-            .users
-            .iter()
-            .map(|u| u.public_key.as_str())
-            .collect::<Vec<&str>>();
-
-        let pubkeys = recipients
-            .iter()
-            .map(|k| Ok(SignedPublicKey::from_string(k)?.0))
-            .collect::<Result<Vec<SignedPublicKey>>>()?;
-
-        let messages = kvpairs
-            .par_iter()
-            .map(|k| encrypt_multi(&k.to_json()?, &pubkeys))
-            .collect::<Result<Vec<String>>>()?;
-
-        let body = json!({
-            "project_id": project_id,
-            "variables": messages,
-        });
-
-        #[derive(Serialize, Deserialize, Debug)]
-        pub struct SetManyVariableReturnType {
-            pub id: String,
-        }
-
-        let url = get_api_url().join("/variables/set-many")?;
-
-        let res = client
-            .post(url)
-            .header(
-                header::AUTHORIZATION,
-                Self::auth_header(partial_fingerprint).await?,
-            )
-            .json(&body)
-            .send()
-            .await?;
-
-        let res = res
-            .json::<Vec<SetManyVariableReturnType>>()
-            .await?
-            .iter()
-            .map(|r| &r.id)
-            .cloned()
-            .collect::<Vec<String>>();
-
-        Ok(res)
+        Ok(Self::client(partial_fingerprint)?
+            .set_many(&kvpairs, project_id)
+            .await?)
     }
 
     pub async fn get_all_variables(
         partial_fingerprint: &str,
     ) -> Result<(Vec<KVPair>, Vec<ParsedPartialVariable>)> {
         // GET /user/:id/variables
-        let config = get_config()?;
-        let key = config.get_key(partial_fingerprint)?;
-
-        let client = reqwest::Client::new();
-
-        let mut url = get_api_url();
-        url.set_path(&format!(
-            "/user/{}/variables",
-            key.uuid.context("No UUID for key, try `envx upload`")?
-        ));
-
-        let encrypted = client
-            .get(url)
-            .header(
-                header::AUTHORIZATION,
-                Self::auth_header(partial_fingerprint).await?,
-            )
-            .send()
+        let encrypted = Self::client(partial_fingerprint)?
+            .all_variables_encrypted()
             .await
-            .context("Failed to get variables")?
-            .json::<Vec<PartialVariable>>()
-            .await
-            .context("Failed to parse API response into PartialVariables")?;
-
-        let decrypted = decrypt_full_many(
-            encrypted
-                .iter()
-                .map(|e| e.value.clone())
-                .collect::<Vec<String>>(),
-            &get_config()?,
-        )?;
+            .context("Failed to get variables")?;
 
-        let parsed = decrypted
-            .iter()
-            .map(|d| KVPair::from_json(d))
-            .collect::<Result<Vec<KVPair>>>()?;
-
-        let partials = decrypted
-            .into_iter()
-            .zip(encrypted.into_iter())
-            .map(move |(d, e)| {
-                Ok(ParsedPartialVariable {
-                    id: e.id,
-                    value: KVPair::from_json(&d)?,
-                    project_id: e.project_id,
-                    created_at: e.created_at,
-                })
-            })
-            .collect::<Result<Vec<ParsedPartialVariable>>>()?;
+        let parsed = decrypt_variables(&encrypted)?;
+        let partials = encrypted.zip_to_parsed(parsed.clone());
 
         Ok((parsed, partials))
     }
 
-    /// You're probably looking for `get_variables_pruned` instead
-    pub async fn get_variables(
+    /// Return the raw, still encrypted variables of a project
+    pub async fn get_variables_encrypted(
         project_id: &str,
         partial_fingerprint: &str,
-    ) -> Result<(Vec<KVPair>, Vec<PartialVariable>)> {
+    ) -> Result<Vec<PartialVariable>> {
         // url : /project/:id/variables
-        let client = reqwest::Client::new();
-
-        let url = get_api_url().join(&format!("/project/{}/variables", project_id))?;
-
-        let encrypted = client
-            .get(url)
-            .header(
-                header::AUTHORIZATION,
-                Self::auth_header(partial_fingerprint).await?,
-            )
-            .send()
-            .await
-            .context("Failed to get variables")?
-            .json::<Vec<PartialVariable>>()
+        Self::client(partial_fingerprint)?
+            .variables_encrypted(project_id)
             .await
-            .context("Failed to parse API response into PartialVariables")?;
+            .context("Failed to get variables")
+    }
 
-        let decrypted = decrypt_full_many(
-            encrypted
-                .iter()
-                .map(|e| e.value.clone())
-                .collect::<Vec<String>>(),
-            &get_config()?,
-        )?;
+    /// You're probably looking for `get_variables_pruned` instead
+    pub async fn get_variables(
+        project_id: &str,
+        partial_fingerprint: &str,
+    ) -> Result<(Vec<KVPair>, Vec<PartialVariable>)> {
+        let encrypted = Self::get_variables_encrypted(project_id, partial_fingerprint).await?;
+        let parsed = decrypt_variables(&encrypted)?;
 
         // splice decrypted and encrypted into a Vector of PartialKey
-        let partials = decrypted
+        let partials = parsed
             .iter()
-            .zip(encrypted.into_iter())
-            .map(|(d, e)| PartialVariable {
-                id: e.id,
-                value: d.clone(),
-                project_id: e.project_id,
-                created_at: e.created_at,
+            .zip(encrypted)
+            .map(|(d, e)| {
+                Ok(PartialVariable {
+                    value: d.to_json()?,
+                    ..e
+                })
             })
-            .collect::<Vec<PartialVariable>>();
-
-        let parsed = decrypted
-            .iter()
-            .map(|d| KVPair::from_json(d))
-            .collect::<Result<Vec<KVPair>>>()?;
+            .collect::<Result<Vec<PartialVariable>>>()?;
 
         Ok((parsed, partials))
     }
@@ -276,7 +185,7 @@ impl SDK {
         let (kvpairs, partial) = Self::get_variables(project_id, partial_fingerprint)
             .await
             .context("Failed to get variables")?;
-        let mut pruned = partial.zip_to_parsed(kvpairs).to_kvpair();
+        let mut pruned = partial.zip_to_parsed(kvpairs).dedupe().to_kvpair();
         pruned.sort_by(|a, b| a.key.cmp(&b.key));
         Ok(pruned)
     }
@@ -286,28 +195,9 @@ impl SDK {
         user_to_get: &str,
     ) -> Result<(String, String)> {
         // url: /user/:id
-        let client = reqwest::Client::new();
-
-        #[derive(Serialize, Deserialize, Debug)]
-        pub struct StrippedUser {
-            pub id: String,
-            pub public_key: String,
-        }
-
-        let url = get_api_url().join("user/")?.join(user_to_get)?;
-
-        let user = client
-            .get(url)
-            .header(
-                header::AUTHORIZATION,
-                Self::auth_header(partial_fingerprint).await?,
-            )
-            .send()
-            .await?
-            .json::<StrippedUser>()
-            .await?;
-
-        Ok((user.id, user.public_key))
+        Ok(Self::client(partial_fingerprint)?
+            .get_user(user_to_get)
+            .await?)
     }
 
     pub async fn add_user_to_project(
@@ -316,138 +206,115 @@ impl SDK {
         project_id: &str,
     ) -> Result<()> {
         // url: /project/:id/add-user
-        let client = reqwest::Client::new();
-
-        let body = json!({
-            "user_id": user_to_add
-        });
+        Self::client(partial_fingerprint)?
+            .add_user_to_project(user_to_add, project_id)
+            .await
+            .context("Failed to add user to project")
+    }
 
-        let url = get_api_url().join(&format!("/project/{}/add-user", project_id))?;
+    pub async fn remove_user_from_project(
+        partial_fingerprint: &str,
+        user_to_remove: &str,
+        project_id: &str,
+    ) -> Result<()> {
+        // url: /project/:id/remove-user
+        Self::client(partial_fingerprint)?
+            .remove_user_from_project(user_to_remove, project_id)
+            .await
+            .context("Failed to remove user from project")
+    }
 
-        let res = client
-            .post(url.join(&format!("/project/{}/add-user", project_id))?)
-            .header(
-                header::AUTHORIZATION,
-                Self::auth_header(partial_fingerprint).await?,
-            )
-            .json(&body)
-            .send()
-            .await?;
+    /// Replace the encrypted values of existing variables, keeping their IDs
+    pub async fn update_many(
+        partials: Vec<PartialVariable>,
+        partial_fingerprint: &str,
+    ) -> Result<Vec<String>> {
+        // url: /variables/update-many
+        Ok(Self::client(partial_fingerprint)?
+            .update_many(&partials)
+            .await?)
+    }
 
-        let status = res.status();
+    /// Re-encrypt every stored version of every variable of a project
+    /// to a new set of armored public keys
+    pub async fn reencrypt_variables(
+        project_id: &str,
+        partial_fingerprint: &str,
+        recipients: &[String],
+    ) -> Result<Vec<String>> {
+        let (kvpairs, mut partials) = Self::get_variables(project_id, partial_fingerprint).await?;
 
-        if status.is_success() {
-            Ok(())
-        } else {
-            bail!("Failed to add user to project: {}", res.text().await?)
+        if partials.is_empty() {
+            return Ok(vec![]);
         }
-    }
 
-    pub async fn delete_project(partial_fingerprint: &str, project_id: &str) -> Result<()> {
-        // url: /project/:id
-        let client = reqwest::Client::new();
+        let pubkeys = recipients
+            .iter()
+            .collect::<HashSet<&String>>()
+            .into_iter()
+            .map(|k| Ok(SignedPublicKey::from_string(k)?.0))
+            .collect::<Result<Vec<SignedPublicKey>>>()?;
 
-        let url = get_api_url().join(&format!("/project/{}", project_id))?;
+        let messages = kvpairs
+            .par_iter()
+            .map(|k| encrypt_multi(&k.to_json()?, &pubkeys))
+            .collect::<Result<Vec<String>, envx::error::Error>>()?;
 
-        let res = client
-            .delete(url)
-            .header(
-                header::AUTHORIZATION,
-                Self::auth_header(partial_fingerprint).await?,
-            )
-            .send()
-            .await?;
+        partials
+            .iter_mut()
+            .zip(messages)
+            .for_each(|(p, m)| p.value = m);
 
-        let status = res.status();
+        Self::update_many(partials, partial_fingerprint).await
+    }
 
-        if status.is_success() {
-            Ok(())
-        } else {
-            bail!("Failed to delete project: {}", res.text().await?)
-        }
+    pub async fn delete_project(partial_fingerprint: &str, project_id: &str) -> Result<()> {
+        // url: /project/:id
+        Self::client(partial_fingerprint)?
+            .delete_project(project_id)
+            .await
+            .context("Failed to delete project")
     }
 
     pub async fn delete_variable(variable_id: &str, partial_fingerprint: &str) -> Result<()> {
         // url: DELETE /variables/:id
-        let client = reqwest::Client::new();
-
-        let url = get_api_url().join("variables/")?.join(variable_id)?;
-
-        client
-            .delete(url)
-            .header(
-                header::AUTHORIZATION,
-                Self::auth_header(partial_fingerprint).await?,
-            )
-            .send()
-            .await?;
-
-        Ok(())
+        Self::client(partial_fingerprint)?
+            .delete_variable(variable_id)
+            .await
+            .context("Failed to delete variable")
     }
 
     pub async fn list_projects(partial_fingerprint: &str) -> Result<Vec<String>> {
         // GET /projects
-        let client = reqwest::Client::new();
-
-        let url = get_api_url().join("projects")?;
-
-        let res = client
-            .get(url)
-            .header(
-                header::AUTHORIZATION,
-                Self::auth_header(partial_fingerprint).await?,
-            )
-            .send()
-            .await
-            .context("Failed to get projects")?;
-
-        let res = res
-            .json::<Vec<String>>()
+        Self::client(partial_fingerprint)?
+            .list_projects()
             .await
-            .context("Failed to parse API response into Vec<String>")?;
-
-        Ok(res)
+            .context("Failed to get projects")
     }
 
     pub async fn new_project(partial_fingerprint: &str) -> Result<String> {
         // POST /projects/new
-        let client = reqwest::Client::new();
-
-        let res = client
-            .post(get_api_url().join("projects/new")?)
-            .header(
-                header::AUTHORIZATION,
-                Self::auth_header(partial_fingerprint).await?,
-            )
-            .send()
-            .await?
-            .text()
-            .await?;
-
-        Ok(res)
+        Ok(Self::client(partial_fingerprint)?.new_project().await?)
     }
+
     pub async fn delete_key(partial_fingerprint: &str) -> Result<()> {
         // DELETE /user/:id
-        let client = reqwest::Client::new();
-
-        let config = get_config()?;
-        let key = config.get_key(partial_fingerprint)?;
-
-        let uuid = key.uuid.context("No UUID for key, try `envx upload`")?;
-
-        let url = get_api_url().join("user/")?.join(&uuid)?;
-
-        client
-            .delete(url)
-            .header(
-                header::AUTHORIZATION,
-                Self::auth_header(partial_fingerprint).await?,
-            )
-            .send()
-            .await?
-            .text()
-            .await?;
-
-        Ok(())
+        Self::client(partial_fingerprint)?
+            .delete_user()
+            .await
+            .context("Failed to delete key from the server")
     }
 }
+
+/// Decrypt with whichever configured key the variables were encrypted to
+fn decrypt_variables(encrypted: &[PartialVariable]) -> Result<Vec<KVPair>> {
+    let decrypted = decrypt_full_many(
+        encrypted.iter().map(|e| e.value.clone()).collect(),
+        &get_config()?,
+    )?;
+
+    Ok(decrypted
+        .iter()
+        .map(|d| KVPair::from_json(d))
+        .collect::<Result<Vec<KVPair>, envx::error::Error>>()?)
+}
@@ -0,0 +1,99 @@
+use super::{
+    error::ApiError,
+    store::{Store, User},
+};
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{header, request::Parts},
+};
+use chrono::{Duration, NaiveDateTime, Utc};
+use pgp::{composed::message::Message, Deserializable, SignedPublicKey};
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// How far the signed timestamp may be from the server's clock
+const MAX_TOKEN_AGE: i64 = 5 * 60;
+
+/// Body of the bearer token built by `crate::client::sign_token`
+#[derive(Deserialize)]
+struct Token {
+    /// ID of the user
+    token: String,
+    /// Armored message containing the current time, signed by the user's key
+    signature: String,
+}
+
+/// The user a request is authenticated as
+pub struct AuthUser(pub User);
+
+#[async_trait]
+impl FromRequestParts<Arc<Store>> for AuthUser {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        store: &Arc<Store>,
+    ) -> Result<Self, Self::Rejection> {
+        let header = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .ok_or_else(|| ApiError::unauthorized("Missing Authorization header"))?
+            .to_str()
+            .map_err(|_| ApiError::unauthorized("Invalid Authorization header"))?;
+
+        let token = header
+            .strip_prefix("Bearer ")
+            .ok_or_else(|| ApiError::unauthorized("Expected a Bearer token"))?;
+        let token = serde_json::from_str::<Token>(token)
+            .map_err(|_| ApiError::unauthorized("Malformed token"))?;
+
+        let user = store
+            .lock()
+            .user(&token.token)
+            .cloned()
+            .ok_or_else(|| ApiError::unauthorized("Unknown user"))?;
+
+        verify(&token.signature, &user.public_key).map_err(ApiError::unauthorized)?;
+
+        Ok(AuthUser(user))
+    }
+}
+
+/// Check the signature and that the signed timestamp is recent
+fn verify(signature: &str, public_key: &str) -> Result<(), String> {
+    let (key, _) = SignedPublicKey::from_string(public_key)
+        .map_err(|_| "Stored public key is invalid".to_string())?;
+    let (message, _) =
+        Message::from_string(signature).map_err(|_| "Malformed signature".to_string())?;
+
+    // `verify` accepts unsigned messages as well
+    if !matches!(message, Message::Signed { .. }) {
+        return Err("Token is not signed".into());
+    }
+    message
+        .verify(&key)
+        .map_err(|_| "Invalid signature".to_string())?;
+
+    let content = message
+        .get_content()
+        .ok()
+        .flatten()
+        .and_then(|c| String::from_utf8(c).ok())
+        .ok_or_else(|| "Signed message has no content".to_string())?;
+
+    // Written with `Utc::now().to_string()`, e.g. 2023-10-01 12:00:00.123456789 UTC
+    let signed_at = NaiveDateTime::parse_from_str(
+        content.trim().trim_end_matches(" UTC"),
+        "%Y-%m-%d %H:%M:%S%.f",
+    )
+    .map_err(|_| "Signed message is not a timestamp".to_string())?
+    .and_utc();
+
+    let age = Utc::now() - signed_at;
+    if age > Duration::seconds(MAX_TOKEN_AGE) || age < Duration::seconds(-MAX_TOKEN_AGE) {
+        return Err("Token expired, check your clock".into());
+    }
+
+    Ok(())
+}
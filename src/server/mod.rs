@@ -0,0 +1,9 @@
+//! Self-hostable stand-in for the envx API, served by `envx-server`
+//!
+//! Serves every endpoint `envx` calls from a JSON data file. Only built with the `server`
+//! feature.
+
+mod auth;
+mod error;
+pub mod routes;
+pub mod store;
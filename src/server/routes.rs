@@ -0,0 +1,370 @@
+use super::{
+    auth::AuthUser,
+    error::{ApiError, ApiResult},
+    store::{generate_id, now, Project, Store, User, Variable},
+};
+use crate::types::ProjectInfo;
+use axum::{
+    extract::{Path, State},
+    routing::{delete, get, post},
+    Json, Router,
+};
+use pgp::{composed::message::Message, Deserializable, SignedPublicKey};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+type AppState = State<Arc<Store>>;
+
+pub fn router(store: Arc<Store>) -> Router {
+    Router::new()
+        .route("/test-auth", post(test_auth))
+        .route("/user/new", post(new_user))
+        .route("/user/:id", get(get_user).delete(delete_user))
+        .route("/user/:id/variables", get(user_variables))
+        .route("/projects", get(list_projects))
+        .route("/projects/new", post(new_project))
+        .route("/project/:id", get(get_project).delete(delete_project))
+        .route("/project/:id/variables", get(project_variables))
+        .route("/project/:id/add-user", post(add_user))
+        .route("/project/:id/remove-user", post(remove_user))
+        .route("/variables/set-many", post(set_many))
+        .route("/variables/update-many", post(update_many))
+        .route("/variables/:id", delete(delete_variable))
+        .with_state(store)
+}
+
+async fn test_auth(AuthUser(user): AuthUser) -> String {
+    format!("Authenticated as {} ({})", user.username, user.id)
+}
+
+#[derive(Deserialize)]
+struct NewUser {
+    username: String,
+    public_key: String,
+}
+
+/// Returns the ID of the new user as plain text
+async fn new_user(State(store): AppState, Json(body): Json<NewUser>) -> ApiResult<String> {
+    if body.username.trim().is_empty() {
+        return Err(ApiError::bad_request("Username can't be empty"));
+    }
+    SignedPublicKey::from_string(&body.public_key)
+        .map_err(|_| ApiError::bad_request("Invalid public key"))?;
+
+    store.update(|data| {
+        let user = User {
+            id: generate_id(),
+            username: body.username,
+            created_at: now(),
+            public_key: body.public_key,
+        };
+        let id = user.id.clone();
+        data.users.push(user);
+        Ok(id)
+    })
+}
+
+/// Any authenticated user can look up a public key, to add its owner to a project
+async fn get_user(
+    State(store): AppState,
+    AuthUser(_): AuthUser,
+    Path(id): Path<String>,
+) -> ApiResult<Json<User>> {
+    let data = store.lock();
+    let user = data
+        .user(&id)
+        .cloned()
+        .ok_or_else(|| ApiError::not_found("User not found"))?;
+    Ok(Json(user))
+}
+
+async fn delete_user(
+    State(store): AppState,
+    AuthUser(user): AuthUser,
+    Path(id): Path<String>,
+) -> ApiResult<()> {
+    if user.id != id {
+        return Err(ApiError::forbidden("You can only delete your own user"));
+    }
+
+    store.update(|data| {
+        data.users.retain(|u| u.id != id);
+        data.projects
+            .iter_mut()
+            .for_each(|p| p.members.retain(|m| *m != id));
+
+        // Nobody can read the variables of a project without members anymore
+        let orphans = data
+            .projects
+            .iter()
+            .filter(|p| p.members.is_empty())
+            .map(|p| p.id.clone())
+            .collect::<Vec<String>>();
+        data.projects.retain(|p| !orphans.contains(&p.id));
+        data.variables.retain(|v| !orphans.contains(&v.project_id));
+        Ok(())
+    })
+}
+
+/// Every variable of every project the user is a member of
+async fn user_variables(
+    State(store): AppState,
+    AuthUser(user): AuthUser,
+    Path(id): Path<String>,
+) -> ApiResult<Json<Vec<Variable>>> {
+    if user.id != id {
+        return Err(ApiError::forbidden("You can only list your own variables"));
+    }
+
+    let data = store.lock();
+    let variables = data
+        .variables
+        .iter()
+        .filter(|v| data.is_member(&v.project_id, &id))
+        .cloned()
+        .collect();
+    Ok(Json(variables))
+}
+
+async fn list_projects(
+    State(store): AppState,
+    AuthUser(user): AuthUser,
+) -> ApiResult<Json<Vec<String>>> {
+    let data = store.lock();
+    let projects = data
+        .projects
+        .iter()
+        .filter(|p| p.members.contains(&user.id))
+        .map(|p| p.id.clone())
+        .collect();
+    Ok(Json(projects))
+}
+
+/// Returns the ID of the new project as plain text
+async fn new_project(State(store): AppState, AuthUser(user): AuthUser) -> ApiResult<String> {
+    store.update(|data| {
+        let project = Project {
+            id: generate_id(),
+            created_at: now(),
+            members: vec![user.id],
+        };
+        let id = project.id.clone();
+        data.projects.push(project);
+        Ok(id)
+    })
+}
+
+async fn get_project(
+    State(store): AppState,
+    AuthUser(user): AuthUser,
+    Path(id): Path<String>,
+) -> ApiResult<Json<ProjectInfo>> {
+    let data = store.lock();
+    let project = member_of(&data, &id, &user)?;
+    let users = project
+        .members
+        .iter()
+        .filter_map(|m| data.user(m).cloned())
+        .collect();
+
+    Ok(Json(ProjectInfo {
+        project_id: project.id.clone(),
+        users,
+    }))
+}
+
+async fn delete_project(
+    State(store): AppState,
+    AuthUser(user): AuthUser,
+    Path(id): Path<String>,
+) -> ApiResult<()> {
+    store.update(|data| {
+        member_of(data, &id, &user)?;
+
+        data.projects.retain(|p| p.id != id);
+        data.variables.retain(|v| v.project_id != id);
+        Ok(())
+    })
+}
+
+async fn project_variables(
+    State(store): AppState,
+    AuthUser(user): AuthUser,
+    Path(id): Path<String>,
+) -> ApiResult<Json<Vec<Variable>>> {
+    let data = store.lock();
+    member_of(&data, &id, &user)?;
+
+    let variables = data
+        .variables
+        .iter()
+        .filter(|v| v.project_id == id)
+        .cloned()
+        .collect();
+    Ok(Json(variables))
+}
+
+#[derive(Deserialize)]
+struct UserId {
+    user_id: String,
+}
+
+async fn add_user(
+    State(store): AppState,
+    AuthUser(user): AuthUser,
+    Path(id): Path<String>,
+    Json(body): Json<UserId>,
+) -> ApiResult<()> {
+    store.update(|data| {
+        member_of(data, &id, &user)?;
+        if data.user(&body.user_id).is_none() {
+            return Err(ApiError::not_found("User not found"));
+        }
+
+        let project = data.project_mut(&id).expect("checked by member_of");
+        if !project.members.contains(&body.user_id) {
+            project.members.push(body.user_id);
+        }
+        Ok(())
+    })
+}
+
+async fn remove_user(
+    State(store): AppState,
+    AuthUser(user): AuthUser,
+    Path(id): Path<String>,
+    Json(body): Json<UserId>,
+) -> ApiResult<()> {
+    store.update(|data| {
+        let project = member_of(data, &id, &user)?;
+        if !project.members.contains(&body.user_id) {
+            return Err(ApiError::not_found("User is not a member of this project"));
+        }
+        if project.members.len() == 1 {
+            return Err(ApiError::bad_request(
+                "Can't remove the last member, delete the project instead",
+            ));
+        }
+
+        let project = data.project_mut(&id).expect("checked by member_of");
+        project.members.retain(|m| *m != body.user_id);
+        Ok(())
+    })
+}
+
+#[derive(Deserialize)]
+struct SetMany {
+    project_id: String,
+    /// Armored PGP messages
+    variables: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct Created {
+    id: String,
+}
+
+async fn set_many(
+    State(store): AppState,
+    AuthUser(user): AuthUser,
+    Json(body): Json<SetMany>,
+) -> ApiResult<Json<Vec<Created>>> {
+    body.variables.iter().try_for_each(|v| check_message(v))?;
+
+    store.update(|data| {
+        member_of(data, &body.project_id, &user)?;
+
+        let created_at = now();
+        let mut created = vec![];
+        for value in body.variables {
+            let variable = Variable {
+                id: generate_id(),
+                value,
+                project_id: body.project_id.clone(),
+                created_at: created_at.clone(),
+            };
+            created.push(Created {
+                id: variable.id.clone(),
+            });
+            data.variables.push(variable);
+        }
+        Ok(Json(created))
+    })
+}
+
+#[derive(Deserialize)]
+struct UpdateMany {
+    variables: Vec<Variable>,
+}
+
+/// Replaces the values of existing variables, keeping their ID, project and creation date
+async fn update_many(
+    State(store): AppState,
+    AuthUser(user): AuthUser,
+    Json(body): Json<UpdateMany>,
+) -> ApiResult<Json<Vec<String>>> {
+    body.variables
+        .iter()
+        .try_for_each(|v| check_message(&v.value))?;
+
+    store.update(|data| {
+        for update in body.variables.iter() {
+            let existing = data
+                .variables
+                .iter()
+                .find(|v| v.id == update.id)
+                .ok_or_else(|| ApiError::not_found(format!("Variable {} not found", update.id)))?;
+            member_of(data, &existing.project_id, &user)?;
+        }
+
+        let mut ids = vec![];
+        for update in body.variables {
+            if let Some(existing) = data.variables.iter_mut().find(|v| v.id == update.id) {
+                existing.value = update.value;
+                ids.push(update.id);
+            }
+        }
+        Ok(Json(ids))
+    })
+}
+
+async fn delete_variable(
+    State(store): AppState,
+    AuthUser(user): AuthUser,
+    Path(id): Path<String>,
+) -> ApiResult<()> {
+    store.update(|data| {
+        let variable = data
+            .variables
+            .iter()
+            .find(|v| v.id == id)
+            .ok_or_else(|| ApiError::not_found("Variable not found"))?;
+        member_of(data, &variable.project_id, &user)?;
+
+        data.variables.retain(|v| v.id != id);
+        Ok(())
+    })
+}
+
+/// The project, if the user is one of its members
+///
+/// Projects the user can't see are reported as missing
+fn member_of<'a>(
+    data: &'a super::store::Data,
+    project_id: &str,
+    user: &User,
+) -> ApiResult<&'a Project> {
+    data.project(project_id)
+        .filter(|p| p.members.contains(&user.id))
+        .ok_or_else(|| ApiError::not_found("Project not found"))
+}
+
+/// Values are stored as is, but they should at least be encrypted messages
+fn check_message(value: &str) -> ApiResult<()> {
+    match Message::from_string(value) {
+        Ok((Message::Encrypted { .. }, _)) => Ok(()),
+        _ => Err(ApiError::bad_request(
+            "Variables must be armored, encrypted PGP messages",
+        )),
+    }
+}
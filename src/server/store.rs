@@ -0,0 +1,135 @@
+// data file = ~/.config/envcli/server/data.json unless --data is given
+
+use crate::{
+    error::{Error, Result},
+    file::write_atomic,
+    paths::config_dir,
+};
+pub use crate::{partial_variable::PartialVariable as Variable, types::User};
+use chrono::{SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{Mutex, MutexGuard},
+};
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Project {
+    pub id: String,
+    pub created_at: String,
+    /// IDs of the users that can read and write the variables
+    pub members: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct Data {
+    pub users: Vec<User>,
+    pub projects: Vec<Project>,
+    pub variables: Vec<Variable>,
+}
+
+impl Data {
+    pub fn user(&self, id: &str) -> Option<&User> {
+        self.users.iter().find(|u| u.id == id)
+    }
+
+    pub fn project(&self, id: &str) -> Option<&Project> {
+        self.projects.iter().find(|p| p.id == id)
+    }
+
+    pub fn project_mut(&mut self, id: &str) -> Option<&mut Project> {
+        self.projects.iter_mut().find(|p| p.id == id)
+    }
+
+    pub fn is_member(&self, project_id: &str, user_id: &str) -> bool {
+        self.project(project_id)
+            .is_some_and(|p| p.members.iter().any(|m| m == user_id))
+    }
+}
+
+/// JSON file holding every user, project and variable
+///
+/// The whole file is rewritten after every change, which is fine for small teams
+pub struct Store {
+    path: PathBuf,
+    data: Mutex<Data>,
+}
+
+impl Store {
+    pub fn open(path: PathBuf) -> Result<Self> {
+        let data = if path.exists() {
+            let contents = fs::read_to_string(&path)
+                .map_err(|e| Error::Io(format!("Failed to read data file: {}", e)))?;
+            serde_json::from_str(&contents)
+                .map_err(|e| Error::Parse(format!("Failed to parse data file: {}", e)))?
+        } else {
+            Data::default()
+        };
+
+        Ok(Self {
+            path,
+            data: Mutex::new(data),
+        })
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, Data> {
+        // A panicking handler can't leave the data half written, see `update`
+        self.data.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Change a copy of the data and only keep it once it is saved
+    ///
+    /// If `change` or the save fail, the data in memory stays the same as the file
+    pub fn update<T, E: From<Error>>(
+        &self,
+        change: impl FnOnce(&mut Data) -> Result<T, E>,
+    ) -> Result<T, E> {
+        let mut data = self.lock();
+        let mut next = data.clone();
+
+        let result = change(&mut next)?;
+        self.save(&next)?;
+        *data = next;
+
+        Ok(result)
+    }
+
+    /// Write to a temporary file first so a crash never leaves a truncated data file
+    fn save(&self, data: &Data) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| Error::Io(format!("Failed to create data directory: {}", e)))?;
+        }
+
+        let contents = serde_json::to_string_pretty(data)
+            .map_err(|e| Error::Parse(format!("Failed to serialize data: {}", e)))?;
+        write_atomic(&self.path, &contents)
+    }
+}
+
+/// `server/data.json` in the directory the envx CLI keeps its config in
+pub fn get_default_location() -> Result<PathBuf> {
+    Ok(config_dir()?.path().join("server").join("data.json"))
+}
+
+/// Random version 4 UUID
+pub fn generate_id() -> String {
+    let mut bytes = rand::random::<[u8; 16]>();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+    let hex = hex::encode(bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+pub fn now() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true)
+}
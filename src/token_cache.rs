@@ -0,0 +1,228 @@
+use crate::{error::Error, file::write_atomic};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    path::PathBuf,
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+/// Servers accept tokens signed up to 5 minutes ago, keep a margin for clock drift
+pub const DEFAULT_TOKEN_LIFETIME: Duration = Duration::from_secs(4 * 60);
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct CachedToken {
+    fingerprint: String,
+    server: String,
+    user_id: String,
+    token: String,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Default)]
+struct Tokens {
+    tokens: Vec<CachedToken>,
+    /// Whether the file has been read yet
+    loaded: bool,
+}
+
+/// Signed tokens, reused until they are about to expire
+///
+/// Kept in memory, and in a file when one is given so that other processes can reuse them.
+/// Cheap to clone, clones share their tokens.
+#[derive(Clone)]
+pub struct TokenCache {
+    inner: Arc<Mutex<Tokens>>,
+    path: Option<PathBuf>,
+    lifetime: Duration,
+}
+
+impl Default for TokenCache {
+    fn default() -> Self {
+        Self::new(DEFAULT_TOKEN_LIFETIME)
+    }
+}
+
+impl TokenCache {
+    pub fn new(lifetime: Duration) -> Self {
+        Self {
+            inner: Arc::default(),
+            path: None,
+            lifetime,
+        }
+    }
+
+    /// Also store the tokens in a file, readable only by the current user
+    pub fn persist_to(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// A token that is still valid for this key, user and server
+    pub fn get(&self, fingerprint: &str, server: &str, user_id: &str) -> Option<String> {
+        let tokens = self.lock();
+        let now = Utc::now();
+
+        tokens
+            .tokens
+            .iter()
+            .find(|t| {
+                t.fingerprint == fingerprint
+                    && t.server == server
+                    && t.user_id == user_id
+                    && t.expires_at > now
+            })
+            .map(|t| t.token.clone())
+    }
+
+    /// Cache a freshly signed token
+    ///
+    /// Failing to write the file only costs a signature later, so it isn't reported
+    pub fn insert(&self, fingerprint: &str, server: &str, user_id: &str, token: &str) {
+        let Ok(lifetime) = chrono::Duration::from_std(self.lifetime) else {
+            return;
+        };
+
+        let mut tokens = self.lock();
+        tokens
+            .tokens
+            .retain(|t| !(t.fingerprint == fingerprint && t.server == server));
+        tokens.tokens.push(CachedToken {
+            fingerprint: fingerprint.into(),
+            server: server.into(),
+            user_id: user_id.into(),
+            token: token.into(),
+            expires_at: Utc::now() + lifetime,
+        });
+
+        let _ = self.save(&mut tokens);
+    }
+
+    /// Drop the token of a key for one server, e.g. after the server rejected it
+    pub fn invalidate(&self, fingerprint: &str, server: &str) {
+        let mut tokens = self.lock();
+        tokens
+            .tokens
+            .retain(|t| !(t.fingerprint == fingerprint && t.server == server));
+        let _ = self.save(&mut tokens);
+    }
+
+    /// Drop every token of a key, in memory and on disk
+    pub fn forget(&self, fingerprint: &str) -> Result<(), Error> {
+        let mut tokens = self.lock();
+        tokens.tokens.retain(|t| t.fingerprint != fingerprint);
+        self.save(&mut tokens)
+    }
+
+    /// Drop every token, in memory and on disk
+    pub fn clear(&self) -> Result<(), Error> {
+        let mut tokens = self.lock();
+        tokens.tokens.clear();
+        self.save(&mut tokens)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Tokens> {
+        let mut tokens = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+
+        if !tokens.loaded {
+            tokens.loaded = true;
+            if let Some(on_disk) = self
+                .path
+                .as_ref()
+                .and_then(|p| fs::read_to_string(p).ok())
+                .and_then(|s| serde_json::from_str::<Vec<CachedToken>>(&s).ok())
+            {
+                tokens.tokens.extend(on_disk);
+            }
+        }
+
+        tokens
+    }
+
+    fn save(&self, tokens: &mut Tokens) -> Result<(), Error> {
+        let now = Utc::now();
+        tokens.tokens.retain(|t| t.expires_at > now);
+
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if tokens.tokens.is_empty() {
+            return match fs::remove_file(path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(Error::Io(format!(
+                    "Failed to remove {}: {}",
+                    path.display(),
+                    e
+                ))),
+                _ => Ok(()),
+            };
+        }
+
+        let json = serde_json::to_string(&tokens.tokens)
+            .map_err(|e| Error::Config(format!("Failed to serialize tokens: {}", e)))?;
+        write_atomic(path, &json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_file(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "envx-tokens-{}-{}-{}.json",
+            name,
+            std::process::id(),
+            rand::random::<u32>()
+        ))
+    }
+
+    #[test]
+    fn tokens_expire_after_their_lifetime() {
+        let cache = TokenCache::new(Duration::from_millis(50));
+        cache.insert("KEY", "https://a", "user", "token");
+        assert_eq!(cache.get("KEY", "https://a", "user").unwrap(), "token");
+
+        // Only for the same key, server and user
+        assert!(cache.get("OTHER", "https://a", "user").is_none());
+        assert!(cache.get("KEY", "https://b", "user").is_none());
+        assert!(cache.get("KEY", "https://a", "someone").is_none());
+
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(cache.get("KEY", "https://a", "user").is_none());
+
+        let cache = TokenCache::new(Duration::ZERO);
+        cache.insert("KEY", "https://a", "user", "token");
+        assert!(cache.get("KEY", "https://a", "user").is_none());
+    }
+
+    #[test]
+    fn forget_drops_the_tokens_of_a_key_on_disk() {
+        let path = temp_file("forget");
+        let cache = TokenCache::default().persist_to(&path);
+        cache.insert("KEY", "https://a", "user", "a");
+        cache.insert("KEY", "https://b", "user", "b");
+        cache.insert("OTHER", "https://a", "other", "c");
+
+        // Another process reads them from the file
+        let other_process = TokenCache::default().persist_to(&path);
+        assert_eq!(other_process.get("KEY", "https://b", "user").unwrap(), "b");
+
+        cache.forget("KEY").unwrap();
+        assert!(cache.get("KEY", "https://a", "user").is_none());
+        assert!(cache.get("KEY", "https://b", "user").is_none());
+        assert_eq!(cache.get("OTHER", "https://a", "other").unwrap(), "c");
+
+        let other_process = TokenCache::default().persist_to(&path);
+        assert!(other_process.get("KEY", "https://a", "user").is_none());
+        assert_eq!(
+            other_process.get("OTHER", "https://a", "other").unwrap(),
+            "c"
+        );
+
+        // The file goes away with the last token
+        cache.forget("OTHER").unwrap();
+        assert!(!path.exists());
+        cache.forget("OTHER").unwrap();
+    }
+}
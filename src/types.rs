@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 //     pub id: String,
 // }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct User {
     pub id: String,
     pub username: String,
@@ -0,0 +1,252 @@
+// Backups of the vault and the config, see `envx backup` and `envx restore`
+
+use super::{
+    config::{Config, Profile},
+    rpgp::get_vault_location,
+    vecu8::ToHex,
+};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use envx::crypto::{decrypt_with_passphrase, encrypt_with_passphrase};
+use envx::file::write_private;
+use pgp::{types::KeyTrait, Deserializable, SignedPublicKey, SignedSecretKey};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs};
+
+/// Format of a backup, bump it when the format changes
+pub const BACKUP_VERSION: u64 = 1;
+
+#[derive(Serialize, Deserialize)]
+pub struct Backup {
+    pub version: u64,
+    pub created_at: DateTime<Utc>,
+    pub config: Config,
+    /// Every directory of the vault
+    pub keys: Vec<BackupKey>,
+}
+
+/// A directory of the vault, named after the fingerprint of its key
+#[derive(Serialize, Deserialize)]
+pub struct BackupKey {
+    pub fingerprint: String,
+    /// File name -> contents
+    pub files: BTreeMap<String, String>,
+}
+
+/// What `Backup::restore` did
+#[derive(Default)]
+pub struct Restored {
+    pub added: Vec<String>,
+    /// Keys already on this machine, they are left as they are
+    pub kept: Vec<String>,
+    /// Keys only had their public key here, the backup added the secret one
+    pub completed: Vec<String>,
+}
+
+impl Backup {
+    /// Everything in the vault, and the config
+    ///
+    /// Without `paths`, the directories linked to projects are left out
+    pub fn collect(mut config: Config, paths: bool) -> Result<Self> {
+        if !paths {
+            config.projects.clear();
+        }
+
+        let mut keys = vec![];
+        let vault = get_vault_location()?;
+        if vault.exists() {
+            for entry in fs::read_dir(&vault).context("Failed to read the vault")? {
+                let entry = entry?;
+                if !entry.file_type()?.is_dir() {
+                    continue;
+                }
+
+                let mut files = BTreeMap::new();
+                for file in fs::read_dir(entry.path())? {
+                    let file = file?;
+                    if file.file_type()?.is_file() {
+                        let contents = fs::read_to_string(file.path())
+                            .with_context(|| format!("Failed to read {}", file.path().display()))?;
+                        files.insert(file.file_name().to_string_lossy().to_string(), contents);
+                    }
+                }
+
+                keys.push(BackupKey {
+                    fingerprint: entry.file_name().to_string_lossy().to_string(),
+                    files,
+                });
+            }
+        }
+        keys.sort_by(|a, b| a.fingerprint.cmp(&b.fingerprint));
+
+        Ok(Self {
+            version: BACKUP_VERSION,
+            created_at: Utc::now(),
+            config,
+            keys,
+        })
+    }
+
+    pub fn encrypt(&self, passphrase: String) -> Result<String> {
+        let json = serde_json::to_string(self).context("Failed to serialize the backup")?;
+        Ok(encrypt_with_passphrase(&json, passphrase)?)
+    }
+
+    /// Decrypt a backup and check that every key in it is intact
+    pub fn decrypt(armored: &str, passphrase: String) -> Result<Self> {
+        let json = decrypt_with_passphrase(armored, passphrase)
+            .context("Failed to decrypt the backup, is the passphrase right?")?;
+        let backup =
+            serde_json::from_str::<Self>(&json).context("The backup is corrupted or invalid")?;
+
+        if backup.version > BACKUP_VERSION {
+            bail!(
+                "The backup was made by a newer version of envx (format {}), update envx first",
+                backup.version
+            );
+        }
+        for key in &backup.keys {
+            key.validate()?;
+        }
+        Ok(backup)
+    }
+
+    /// Merge into the vault and the config, keys already here win over the backup
+    ///
+    /// Does not write the config. Call it inside `Config::update` to write to disk
+    pub fn restore(self, config: &mut Config) -> Result<Restored> {
+        let mut restored = Restored::default();
+        let fresh = config.keys.is_empty();
+        let vault = get_vault_location()?;
+
+        for key in &self.keys {
+            let dir = vault.join(&key.fingerprint);
+            let existed = dir.exists();
+            fs::create_dir_all(&dir).context("Failed to create key directory")?;
+
+            let mut wrote_secret = false;
+            for (name, contents) in &key.files {
+                let path = dir.join(name);
+                if !path.exists() {
+                    let written = if name == "private.key" {
+                        write_private(&path, contents).map_err(anyhow::Error::from)
+                    } else {
+                        fs::write(&path, contents).map_err(anyhow::Error::from)
+                    };
+                    written.with_context(|| format!("Failed to write {}", path.display()))?;
+                    wrote_secret |= name == "private.key";
+                }
+            }
+
+            if !existed {
+                restored.added.push(key.fingerprint.clone());
+            } else if wrote_secret {
+                restored.completed.push(key.fingerprint.clone());
+            } else {
+                restored.kept.push(key.fingerprint.clone());
+            }
+        }
+
+        for key in self.config.keys {
+            match config
+                .keys
+                .iter_mut()
+                .find(|k| k.fingerprint == key.fingerprint)
+            {
+                Some(local) => {
+                    if local.is_pubkey_only()
+                        && vault.join(&local.fingerprint).join("private.key").exists()
+                    {
+                        local.pubkey_only = None;
+                    }
+                    if local.uuid.is_none() {
+                        local.uuid = key.uuid;
+                    }
+                    if local.note.is_empty() {
+                        local.note = key.note;
+                    }
+                }
+                None => config.keys.push(key),
+            }
+        }
+
+        if config.primary_key.is_empty() {
+            config.primary_key = self.config.primary_key;
+        }
+
+        for (name, profile) in self.config.profiles {
+            let local = config.profiles.entry(name).or_insert_with(|| Profile {
+                url: profile.url.clone(),
+                uuids: BTreeMap::new(),
+                default_key: None,
+            });
+            for (fingerprint, uuid) in profile.uuids {
+                local.uuids.entry(fingerprint).or_insert(uuid);
+            }
+            if local.default_key.is_none() {
+                local.default_key = profile.default_key;
+            }
+        }
+
+        for project in self.config.projects {
+            if !config
+                .projects
+                .iter()
+                .any(|p| p.project_id == project.project_id && p.path == project.path)
+            {
+                config.projects.push(project);
+            }
+        }
+
+        // The hashed user IDs of the keys depend on the salt, so a new install takes it
+        // along with the settings
+        if fresh {
+            config.salt = self.config.salt;
+            config.online = self.config.online;
+            config.sdk_url = self.config.sdk_url;
+            config.settings = self.config.settings;
+        }
+
+        Ok(restored)
+    }
+}
+
+impl BackupKey {
+    /// The keys must parse and belong to the fingerprint of their directory
+    fn validate(&self) -> Result<()> {
+        // Both become paths in the vault
+        if self.fingerprint.is_empty() || !self.fingerprint.chars().all(|c| c.is_ascii_hexdigit()) {
+            bail!("Invalid key directory {:?} in the backup", self.fingerprint);
+        }
+        if let Some(name) = self
+            .files
+            .keys()
+            .find(|name| name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']))
+        {
+            bail!("Invalid file name {:?} in the backup", name);
+        }
+
+        if let Some(public_key) = self.files.get("public.key") {
+            let (key, _) = SignedPublicKey::from_string(public_key)
+                .with_context(|| format!("Invalid public key for {}", self.fingerprint))?;
+            self.check_fingerprint(key.fingerprint())?;
+        }
+        if let Some(secret_key) = self.files.get("private.key") {
+            let (key, _) = SignedSecretKey::from_string(secret_key)
+                .with_context(|| format!("Invalid secret key for {}", self.fingerprint))?;
+            self.check_fingerprint(key.fingerprint())?;
+        }
+        Ok(())
+    }
+
+    fn check_fingerprint(&self, fingerprint: Vec<u8>) -> Result<()> {
+        if !fingerprint.to_hex().eq_ignore_ascii_case(&self.fingerprint) {
+            bail!(
+                "The key stored for {} has fingerprint {}",
+                self.fingerprint,
+                fingerprint.to_hex()
+            );
+        }
+        Ok(())
+    }
+}
@@ -1,5 +1,6 @@
-use super::{config::Config, key::Key, kvpair::KVPair, settings::Settings};
+use super::{config::Config, key::Key, settings::Settings};
 use anyhow::{Context, Result};
+use envx::kvpair::KVPair;
 use serde_json::Value;
 use std::collections::BTreeMap;
 
@@ -7,6 +8,7 @@ pub trait ToBTreeMap {
     fn to_btreemap(&self) -> Result<BTreeMap<String, String>>;
 }
 
+#[allow(dead_code)]
 pub trait FromBTreeMap {
     fn from_btreemap(map: &BTreeMap<String, String>) -> Result<Self>
     where
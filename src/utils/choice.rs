@@ -5,6 +5,8 @@ use crate::sdk::SDK;
 use super::{
     config::{get_config, Config},
     key::Key,
+    local_store::LocalProject,
+    resolve,
 };
 
 pub struct Choice {}
@@ -17,7 +19,14 @@ impl Choice {
 
     pub async fn choose_project(partial_fingerprint: &str) -> Result<String> {
         let (key, config) = Self::get_key(partial_fingerprint)?;
-        let all_projects = SDK::list_projects(&key.fingerprint).await?;
+        let all_projects = if config.online {
+            SDK::list_projects(&key.fingerprint).await?
+        } else {
+            LocalProject::list()?
+                .into_iter()
+                .map(|p| p.project_id)
+                .collect()
+        };
 
         let local_projects = config.projects.clone();
 
@@ -31,8 +40,9 @@ impl Choice {
             .map(|p| format!("{} - {}", p.project_id, p.path.to_str().unwrap()))
             .collect::<Vec<_>>();
 
+        let origin = if config.online { "Remote" } else { "Offline" };
         all_projects.iter().for_each(|p| {
-            options.push(format!("{} - {}", p, "Remote"));
+            options.push(format!("{} - {}", p, origin));
         });
 
         let selected = crate::utils::prompt::prompt_options("Select project", options)?;
@@ -54,17 +64,28 @@ impl Choice {
         project_id: Option<String>,
         partial_fingerprint: &str,
     ) -> Result<String> {
-        match project_id {
-            Some(p) => Ok(p),
-            None => {
-                let config = get_config().context("Failed to get config")?;
-                let project = config.get_project();
-
-                match project {
-                    Ok(p) => Ok(p.project_id.clone()),
-                    Err(_) => Self::choose_project(partial_fingerprint).await,
-                }
-            }
+        let config = get_config().context("Failed to get config")?;
+
+        match resolve::project(&config, project_id)? {
+            Some(p) => Ok(p.value),
+            None => Self::choose_project(partial_fingerprint).await,
+        }
+    }
+
+    /// The environment given on the command line, or the default of the repository
+    /// or directory, if it is linked to the same project
+    pub fn try_environment(
+        environment: Option<String>,
+        project_id: &str,
+    ) -> Result<Option<String>> {
+        if environment.is_some() {
+            return Ok(environment);
         }
+
+        let config = get_config().context("Failed to get config")?;
+        Ok(resolve::links(&config)?
+            .iter()
+            .filter(|link| link.project_id() == project_id)
+            .find_map(|link| link.environment().cloned()))
     }
 }
@@ -1,19 +1,32 @@
-// configuration path = ~/.config/envcli/config.json
+// configuration path = ~/.config/envcli/config.json, see `resolve::config_dir`
 
 use super::key::Key;
+use super::resolve::{self, active_profile, config_dir};
 use super::rpgp::get_vault_location;
 use super::settings::Settings;
+use crate::constants::DEFAULT_API_URL;
 use anyhow::anyhow;
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use colored::Colorize;
-use home::home_dir;
+use envx::file::write_atomic;
+use fs2::FileExt;
 use serde::{Deserialize, Serialize};
-use std::fs::{self, File};
-use std::io::{BufWriter, Write};
-use std::path::PathBuf;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::path::{Path, PathBuf};
+
+/// Format of config.json, bump it and add a step to `migrate` when the format changes
+pub const CONFIG_VERSION: u64 = 1;
+
+/// The server of `sdk_url`, with the UUIDs stored on the keys themselves
+pub const DEFAULT_PROFILE: &str = "default";
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
+    /// Format of the file, see `CONFIG_VERSION`
+    #[serde(default)]
+    pub version: u64,
     /// TODO: rethink Salting hashes
     pub salt: String,
     /// The fingerprint of the primary signing key
@@ -28,12 +41,30 @@ pub struct Config {
     pub settings: Option<Settings>,
     /// Projects
     pub projects: Vec<Project>,
+    /// Other servers, selected with `--profile` or `ENVX_PROFILE`
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>,
+}
+
+/// A server, and the user IDs the keys were uploaded as on it
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Profile {
+    pub url: String,
+    /// Fingerprint of a key -> ID of its user on this server
+    #[serde(default)]
+    pub uuids: BTreeMap<String, String>,
+    /// Used instead of the primary key when no key is given
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_key: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Project {
     pub project_id: String,
     pub path: PathBuf,
+    /// Default environment for this directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<String>,
 }
 
 impl Default for Config {
@@ -41,32 +72,53 @@ impl Default for Config {
         let salt = rand::random::<[u8; 32]>();
         let salt = hex::encode(salt);
         Self {
+            version: CONFIG_VERSION,
             salt,
             primary_key: "".into(),
             keys: vec![],
             online: true,
-            sdk_url: Some("https://api.env-cli.com".into()),
+            sdk_url: Some(DEFAULT_API_URL.into()),
             settings: None,
             projects: vec![],
+            profiles: BTreeMap::new(),
         }
     }
 }
 
 impl Config {
-    /// Vulnerable to fs race conditions
-    /// should rewrite using file locks
-    pub fn write(&self) -> Result<()> {
+    /// Read config.json, change it and write it back
+    ///
+    /// The config stays locked from the read to the write, so concurrent commands apply
+    /// their changes one after the other instead of overwriting each other's
+    pub fn update<T>(change: impl FnOnce(&mut Config) -> Result<T>) -> Result<T> {
+        // Migrates older files first, which takes the lock itself
+        get_config()?;
+
         let path = get_config_path().context("Failed to get config path")?;
-        let file = File::create(path).context("Failed to create config file")?;
-        let mut writer = BufWriter::new(file);
-        let contents = serde_json::to_string_pretty(self)
-            .context("Failed to serialize config to JSON string")?;
+        let _lock = lock_config(&path)?;
+        let mut config = serde_json::from_value::<Config>(read_config_value(&path)?)
+            .context("Failed to parse config file")?;
 
-        writer
-            .write_all(contents.as_bytes())
-            .context("Failed to write config to file")?;
+        let result = change(&mut config)?;
+        config.write(&path)?;
+        Ok(result)
+    }
 
-        Ok(())
+    /// Replace config.json, keeping the previous file as config.json.bak
+    ///
+    /// Readers never see a partial file. Only called by `update`, which holds the lock
+    fn write(&self, path: &Path) -> Result<()> {
+        let contents = serde_json::to_string_pretty(&Config {
+            version: CONFIG_VERSION,
+            ..self.clone()
+        })
+        .context("Failed to serialize config to JSON string")?;
+
+        if path.exists() {
+            fs::copy(path, path.with_extension("json.bak"))
+                .context("Failed to back up config file")?;
+        }
+        write_atomic(path, &contents).context("Failed to write config to file")
     }
 
     #[allow(dead_code)]
@@ -86,7 +138,7 @@ impl Config {
     ///
     /// - Returns an error if the key doesn't exist
     ///
-    /// Does not write to disk. Call `config.write()` to write to disk
+    /// Does not write to disk. Call it inside `Config::update` to write to disk
     pub fn set_primary_key(&mut self, fingerprint: &str) -> Result<()> {
         let key = self.get_key(fingerprint)?;
         self.primary_key = key.fingerprint.clone();
@@ -121,10 +173,7 @@ impl Config {
     }
 
     pub fn get_key_or_default(&self, partial_fingerprint: Option<String>) -> Result<Key> {
-        let partial_fingerprint = match partial_fingerprint {
-            Some(p) => p,
-            None => self.primary_key.clone(),
-        };
+        let partial_fingerprint = resolve::key(self, partial_fingerprint)?.value;
         if partial_fingerprint.is_empty() {
             return Err(anyhow::anyhow!("No key provided"));
         }
@@ -147,6 +196,7 @@ impl Config {
         let project = Project {
             project_id: project_id.to_string(),
             path,
+            environment: None,
         };
 
         self.projects.push(project);
@@ -168,11 +218,12 @@ impl Config {
         Err(anyhow::anyhow!("Failed to find project"))
     }
 
-    pub fn set_project(&mut self, project_id: &str) -> Result<()> {
+    pub fn set_project(&mut self, project_id: &str, environment: Option<String>) -> Result<()> {
         let path = std::env::current_dir()?;
         let new_project = Project {
             project_id: project_id.to_string(),
             path,
+            environment,
         };
 
         self.projects.push(new_project);
@@ -207,33 +258,177 @@ impl Config {
         Ok(())
     }
 
-    pub fn set_uuid(&mut self, fingerprint: &str, uuid: &str) -> Result<()> {
-        let mut key = Self::get_key(self, fingerprint)?.clone();
-        self.keys.retain(|k| k.fingerprint != fingerprint);
-        key.uuid = Some(uuid.to_string());
-        self.keys.push(key);
+    /// The active profile, `None` for the default one
+    pub fn profile(&self) -> Result<Option<&Profile>> {
+        let name = active_profile();
+        if name == DEFAULT_PROFILE {
+            return Ok(None);
+        }
+
+        match self.profiles.get(&name) {
+            Some(profile) => Ok(Some(profile)),
+            None => Err(anyhow!(
+                "Unknown profile {}, add it with `envx config profile add {} --url <URL>`",
+                name,
+                name
+            )),
+        }
+    }
+
+    /// ID the key was uploaded as on the server of the active profile
+    pub fn key_uuid(&self, key: &Key) -> Result<Option<String>> {
+        Ok(match self.profile()? {
+            Some(profile) => profile.uuids.get(&key.fingerprint).cloned(),
+            None => key.uuid.clone(),
+        })
+    }
+
+    /// Record the ID a key was uploaded as, on the active profile only
+    ///
+    /// Does not write to disk. Call it inside `Config::update` to write to disk
+    pub fn set_key_uuid(&mut self, fingerprint: &str, uuid: &str) -> Result<()> {
+        let fingerprint = self.get_key(fingerprint)?.fingerprint;
+        let name = active_profile();
+
+        if name == DEFAULT_PROFILE {
+            self.keys
+                .iter_mut()
+                .filter(|k| k.fingerprint == fingerprint)
+                .for_each(|k| k.uuid = Some(uuid.to_string()));
+        } else {
+            self.profile()?;
+            let profile = self.profiles.get_mut(&name).expect("checked by profile");
+            profile.uuids.insert(fingerprint, uuid.to_string());
+        }
+        Ok(())
+    }
+
+    /// Forget the ID of a key on the active profile, e.g. after its user was deleted
+    ///
+    /// Does not write to disk. Call it inside `Config::update` to write to disk
+    pub fn unset_key_uuid(&mut self, fingerprint: &str) -> Result<()> {
+        let fingerprint = self.get_key(fingerprint)?.fingerprint;
+        let name = active_profile();
+
+        if name == DEFAULT_PROFILE {
+            self.keys
+                .iter_mut()
+                .filter(|k| k.fingerprint == fingerprint)
+                .for_each(|k| k.uuid = None);
+        } else if let Some(profile) = self.profiles.get_mut(&name) {
+            profile.uuids.remove(&fingerprint);
+        }
         Ok(())
     }
 }
 
 /// Get the configuration path ~/.config/envcli/config.json
 pub fn get_config_path() -> Result<PathBuf> {
-    let mut path = home_dir().context("Failed to get home directory")?;
-    path.push(".config/envcli/config.json");
+    let path = config_dir()?.value.join("config.json");
     // if it doesn't exist, create it
     if !path.exists() {
-        let default = serde_json::to_string_pretty(&Config::default())?;
         let parent_path = path.parent().context("Failed to get parent directory")?;
         fs::create_dir_all(parent_path)?;
-        let mut file = File::create(&path)?;
-        file.write_all(default.as_ref())?;
+
+        let _lock = lock_config(&path)?;
+        // Another process may have created it while we waited for the lock
+        if !path.exists() {
+            let default = serde_json::to_string_pretty(&Config::default())?;
+            write_atomic(&path, &default)?;
+        }
     }
     Ok(path)
 }
 
+/// Signed auth tokens shared between runs, ~/.config/envcli/tokens.json
+pub fn get_token_cache_path() -> Result<PathBuf> {
+    let config_path = get_config_path()?;
+    let parent = config_path
+        .parent()
+        .context("Failed to get parent directory")?;
+    Ok(parent.join("tokens.json"))
+}
+
 /// Read the configuration file and parse it into a Config struct
+///
+/// Files written by older versions are migrated, the original is kept as config.json.v<N>.bak
 pub fn get_config() -> Result<Config> {
     let path = get_config_path().context("Failed to get config path")?;
+    let value = read_config_value(&path)?;
+
+    let version = file_version(&value)?;
+    if version == CONFIG_VERSION {
+        return serde_json::from_value(value).context("Failed to parse config file");
+    }
+
+    let _lock = lock_config(&path)?;
+    // Another process may have migrated it while we waited for the lock
+    let value = read_config_value(&path)?;
+    let version = file_version(&value)?;
+    if version == CONFIG_VERSION {
+        return serde_json::from_value(value).context("Failed to parse config file");
+    }
+
+    let backup = path.with_extension(format!("json.v{}.bak", version));
+    fs::copy(&path, &backup).context("Failed to back up config file")?;
+
+    let config = serde_json::from_value::<Config>(migrate(value, version)?)
+        .context("Failed to parse migrated config file")?;
+    let contents = serde_json::to_string_pretty(&config)?;
+    write_atomic(&path, &contents).context("Failed to write migrated config file")?;
+
+    eprintln!(
+        "Migrated {} to format {}, the previous file is at {}",
+        path.display(),
+        CONFIG_VERSION,
+        backup.display()
+    );
+    Ok(config)
+}
+
+fn read_config_value(path: &Path) -> Result<Value> {
     let contents = fs::read_to_string(path).context("Failed to read config file")?;
-    serde_json::from_str::<Config>(&contents).context("Failed to parse config file")
+    serde_json::from_str::<Value>(&contents).context("Failed to parse config file")
+}
+
+/// Files from before versioning have no `version`
+fn file_version(value: &Value) -> Result<u64> {
+    let version = value.get("version").and_then(Value::as_u64).unwrap_or(0);
+    if version > CONFIG_VERSION {
+        bail!(
+            "The config file is in format {}, this version of envx only reads up to {}, please upgrade",
+            version,
+            CONFIG_VERSION
+        );
+    }
+    Ok(version)
+}
+
+/// Bring a config file from `version` up to `CONFIG_VERSION`, one format at a time
+fn migrate(mut value: Value, mut version: u64) -> Result<Value> {
+    while version < CONFIG_VERSION {
+        match version {
+            // Only adds `version`, every other new field has a default
+            0 => {}
+            _ => bail!("Don't know how to migrate config format {}", version),
+        }
+        version += 1;
+    }
+
+    value["version"] = Value::from(version);
+    Ok(value)
+}
+
+/// Exclusive lock on config.json.lock, released when dropped
+fn lock_config(path: &Path) -> Result<File> {
+    let lock_path = path.with_extension("json.lock");
+    let lock = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .open(&lock_path)
+        .with_context(|| format!("Failed to open {}", lock_path.display()))?;
+    lock.lock_exclusive()
+        .with_context(|| format!("Failed to lock {}", lock_path.display()))?;
+    Ok(lock)
 }
@@ -0,0 +1,429 @@
+use anyhow::{bail, Result};
+use crypto_hash::{hex_digest, Algorithm};
+use envx::kvpair::KVPair;
+use std::{iter::Peekable, str::Chars};
+
+const HEADER_PREFIX: &str = "# Generated by envx";
+const CHECKSUM_PREFIX: &str = "# checksum: ";
+
+/// Quote a value so it survives a round trip through dotenv parsers
+///
+/// Plain values are left as they are. Single line values without single quotes
+/// are single quoted, so `#`, `=`, `$` and double quotes are taken literally.
+/// Anything else is double quoted with backslashes, quotes and line breaks escaped
+pub fn quote(value: &str) -> String {
+    let plain = !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-./:@,+%".contains(c));
+    if plain {
+        return value.to_string();
+    }
+
+    if !value.contains(['\'', '\n', '\r']) {
+        return format!("'{}'", value);
+    }
+
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+
+    quoted
+}
+
+/// Serialize variables as a dotenv file body, one `KEY=value` per line
+pub fn serialize(kvpairs: &[KVPair]) -> String {
+    kvpairs
+        .iter()
+        .map(|kv| format!("{}={}\n", kv.key, quote(&kv.value)))
+        .collect()
+}
+
+fn checksum(body: &str) -> String {
+    hex_digest(Algorithm::SHA256, body.as_bytes())
+}
+
+/// Serialize variables with a header recording where they came from
+/// and a checksum of the body, used to detect local edits
+pub fn serialize_with_header(kvpairs: &[KVPair], project_id: &str) -> String {
+    let body = serialize(kvpairs);
+    format!(
+        "{} from project {}, do not edit\n{}{}\n{}",
+        HEADER_PREFIX,
+        project_id,
+        CHECKSUM_PREFIX,
+        checksum(&body),
+        body
+    )
+}
+
+/// True if the file was written by `serialize_with_header` and not edited since
+pub fn is_unmodified(contents: &str) -> bool {
+    let mut lines = contents.splitn(3, '\n');
+    let (Some(header), Some(checksum_line)) = (lines.next(), lines.next()) else {
+        return false;
+    };
+    let body = lines.next().unwrap_or_default();
+
+    header.starts_with(HEADER_PREFIX)
+        && checksum_line.strip_prefix(CHECKSUM_PREFIX) == Some(checksum(body).as_str())
+}
+
+/// Parse a dotenv file into variables
+///
+/// Supports `export` prefixes, comments, single quoted (literal) and double quoted
+/// (escaped) values, and quoted values spanning multiple lines. Later
+/// definitions of a key override earlier ones
+pub fn parse(contents: &str) -> Result<Vec<KVPair>> {
+    let mut parser = Parser {
+        chars: contents.chars().peekable(),
+        line: 1,
+    };
+    let mut kvpairs: Vec<KVPair> = vec![];
+
+    while let Some(kvpair) = parser.next_pair()? {
+        kvpairs.retain(|k| k.key != kvpair.key);
+        kvpairs.push(kvpair);
+    }
+
+    Ok(kvpairs)
+}
+
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+}
+
+impl Parser<'_> {
+    fn bump(&mut self) -> Option<char> {
+        let c = self.chars.next();
+        if c == Some('\n') {
+            self.line += 1;
+        }
+        c
+    }
+
+    fn skip_blanks(&mut self) {
+        while matches!(self.chars.peek(), Some(' ' | '\t')) {
+            self.bump();
+        }
+    }
+
+    fn skip_line(&mut self) {
+        while let Some(c) = self.bump() {
+            if c == '\n' {
+                break;
+            }
+        }
+    }
+
+    /// Only whitespace or a comment may follow a value on the same line
+    fn end_of_line(&mut self) -> Result<()> {
+        self.skip_blanks();
+        match self.chars.peek() {
+            None | Some('\n') | Some('#') => {
+                self.skip_line();
+                Ok(())
+            }
+            Some('\r') => {
+                self.bump();
+                self.end_of_line()
+            }
+            Some(c) => bail!(
+                "Line {}: unexpected character '{}' after value",
+                self.line,
+                c
+            ),
+        }
+    }
+
+    fn word(&mut self) -> String {
+        let mut word = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if c.is_whitespace() || c == '=' {
+                break;
+            }
+            word.push(c);
+            self.bump();
+        }
+        word
+    }
+
+    fn next_pair(&mut self) -> Result<Option<KVPair>> {
+        loop {
+            match self.chars.peek() {
+                None => return Ok(None),
+                Some(c) if c.is_whitespace() => {
+                    self.bump();
+                }
+                Some('#') => self.skip_line(),
+                Some(_) => break,
+            }
+        }
+
+        let line = self.line;
+        let mut key = self.word();
+        if key == "export" && matches!(self.chars.peek(), Some(' ' | '\t')) {
+            self.skip_blanks();
+            key = self.word();
+        }
+
+        let valid_key = key
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+        if !valid_key {
+            bail!("Line {}: invalid variable name '{}'", line, key);
+        }
+
+        self.skip_blanks();
+        if self.bump() != Some('=') {
+            bail!("Line {}: expected '=' after {}", line, key);
+        }
+        self.skip_blanks();
+
+        let value = match self.chars.peek() {
+            Some('"') => {
+                self.bump();
+                let value = self.double_quoted(line)?;
+                self.end_of_line()?;
+                value
+            }
+            Some('\'') => {
+                self.bump();
+                let value = self.single_quoted(line)?;
+                self.end_of_line()?;
+                value
+            }
+            _ => self.unquoted(),
+        };
+
+        Ok(Some(KVPair::new(key.to_uppercase(), value)))
+    }
+
+    fn double_quoted(&mut self, line: usize) -> Result<String> {
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                None => bail!("Line {}: unterminated double quoted value", line),
+                Some('"') => return Ok(value),
+                Some('\\') => match self.bump() {
+                    Some('n') => value.push('\n'),
+                    Some('r') => value.push('\r'),
+                    Some('t') => value.push('\t'),
+                    Some(c @ ('\\' | '"' | '\'' | '$' | '`')) => value.push(c),
+                    Some('\n') => {}
+                    Some(c) => {
+                        value.push('\\');
+                        value.push(c);
+                    }
+                    None => bail!("Line {}: unterminated double quoted value", line),
+                },
+                Some(c) => value.push(c),
+            }
+        }
+    }
+
+    fn single_quoted(&mut self, line: usize) -> Result<String> {
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                None => bail!("Line {}: unterminated single quoted value", line),
+                Some('\'') => return Ok(value),
+                Some(c) => value.push(c),
+            }
+        }
+    }
+
+    /// Runs to the end of the line, a `#` preceded by whitespace starts a comment
+    fn unquoted(&mut self) -> String {
+        let mut value = String::new();
+        while let Some(c) = self.bump() {
+            match c {
+                '\n' => break,
+                '#' if value.is_empty() || value.ends_with([' ', '\t']) => {
+                    self.skip_line();
+                    break;
+                }
+                c => value.push(c),
+            }
+        }
+        value.trim_end().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(kvpairs: &[KVPair]) -> Vec<(&str, &str)> {
+        kvpairs
+            .iter()
+            .map(|k| (k.key.as_str(), k.value.as_str()))
+            .collect()
+    }
+
+    #[test]
+    fn export_prefix_and_comments() {
+        let parsed = parse(
+            "# database\n\
+             export DB_HOST=localhost\n\
+             \texport   DB_PORT = 5432 # default port\n\
+             URL=http://example.com/#anchor\n\
+             HASH=# only a comment\n\
+             exported=1\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            pairs(&parsed),
+            [
+                ("DB_HOST", "localhost"),
+                ("DB_PORT", "5432"),
+                ("URL", "http://example.com/#anchor"),
+                ("HASH", ""),
+                ("EXPORTED", "1"),
+            ]
+        );
+    }
+
+    #[test]
+    fn quoted_values() {
+        let parsed = parse(concat!(
+            "SINGLE='literal \\n $HOME # not a comment'\n",
+            "DOUBLE=\"tab\\there \\\"quoted\\\" \\\\ \\$ \\q\"\n",
+            "TRAILING=\"value\"   # comment\n",
+        ))
+        .unwrap();
+
+        assert_eq!(
+            pairs(&parsed),
+            [
+                ("SINGLE", "literal \\n $HOME # not a comment"),
+                ("DOUBLE", "tab\there \"quoted\" \\ $ \\q"),
+                ("TRAILING", "value"),
+            ]
+        );
+    }
+
+    #[test]
+    fn multi_line_values() {
+        let parsed = parse(concat!(
+            "KEY=\"-----BEGIN KEY-----\n",
+            "abc\n",
+            "-----END KEY-----\"\n",
+            "LITERAL='one\n",
+            "two'\n",
+            "ESCAPED=\"first\\nsecond\"\n",
+            "CONTINUED=\"joined \\\n",
+            "line\"\n",
+        ))
+        .unwrap();
+
+        assert_eq!(
+            pairs(&parsed),
+            [
+                ("KEY", "-----BEGIN KEY-----\nabc\n-----END KEY-----"),
+                ("LITERAL", "one\ntwo"),
+                ("ESCAPED", "first\nsecond"),
+                ("CONTINUED", "joined line"),
+            ]
+        );
+    }
+
+    #[test]
+    fn crlf_and_empty_values() {
+        let parsed = parse("A=1\r\nB='2'\r\nEMPTY=\r\nQUOTED=\"\"\r\nBLANK=   \r\n").unwrap();
+
+        assert_eq!(
+            pairs(&parsed),
+            [
+                ("A", "1"),
+                ("B", "2"),
+                ("EMPTY", ""),
+                ("QUOTED", ""),
+                ("BLANK", "")
+            ]
+        );
+    }
+
+    #[test]
+    fn later_definitions_win() {
+        let parsed = parse("A=1\nB=2\nA=3\n").unwrap();
+        assert_eq!(pairs(&parsed), [("B", "2"), ("A", "3")]);
+    }
+
+    #[test]
+    fn invalid_files() {
+        for (contents, error) in [
+            ("1KEY=value", "Line 1: invalid variable name"),
+            ("A=1\nKEY value", "Line 2: expected '='"),
+            ("A=\"open\nstill open", "Line 1: unterminated double quoted"),
+            ("A='open", "Line 1: unterminated single quoted"),
+            ("A='quoted' trailing", "Line 1: unexpected character 't'"),
+        ] {
+            let err = parse(contents).unwrap_err().to_string();
+            assert!(err.starts_with(error), "{:?}: {}", contents, err);
+        }
+    }
+
+    #[test]
+    fn quote_picks_the_simplest_form() {
+        assert_eq!(quote("plain-value_1.2/3:4@5"), "plain-value_1.2/3:4@5");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("has space # and $HOME"), "'has space # and $HOME'");
+        assert_eq!(quote("it's"), "\"it's\"");
+        assert_eq!(quote("a\nb"), "\"a\\nb\"");
+    }
+
+    #[test]
+    fn round_trip() {
+        let kvpairs = [
+            ("PLAIN", "value"),
+            ("EMPTY", ""),
+            ("SPACES", "  leading and trailing  "),
+            ("COMMENT", "value # not a comment"),
+            ("HASH", "#start"),
+            ("SINGLE", "it's"),
+            ("DOUBLE", "say \"hi\""),
+            ("BOTH", "'single' and \"double\""),
+            ("BACKSLASH", "C:\\path\\to\\n"),
+            ("MULTI", "line one\nline two\r\nline three\n"),
+            ("TAB", "a\tb"),
+            ("DOLLAR", "$HOME ${USER} `cmd`"),
+            ("EQUALS", "a=b=c"),
+            ("UNICODE", "héllo wörld ✓"),
+        ]
+        .map(|(k, v)| KVPair::new(k.to_string(), v.to_string()));
+
+        let parsed = parse(&serialize(&kvpairs)).unwrap();
+        assert_eq!(pairs(&parsed), pairs(&kvpairs));
+    }
+
+    #[test]
+    fn header_checksum_detects_edits() {
+        let kvpairs = [KVPair::new("A".into(), "1".into())];
+        let contents = serialize_with_header(&kvpairs, "project");
+
+        assert!(is_unmodified(&contents));
+        assert_eq!(pairs(&parse(&contents).unwrap()), [("A", "1")]);
+
+        assert!(!is_unmodified(&contents.replace("A=1", "A=2")));
+        assert!(!is_unmodified(&format!("{}B=2\n", contents)));
+        assert!(!is_unmodified(&serialize(&kvpairs)));
+        assert!(!is_unmodified(""));
+    }
+}
@@ -0,0 +1,38 @@
+use anyhow::{Context, Result};
+use std::{fs, path::Path};
+
+/// Add an entry to the .gitignore next to `path`, if it isn't already there
+///
+/// Returns true if the entry was added
+pub fn add_to_gitignore(path: &Path) -> Result<bool> {
+    let name = path
+        .file_name()
+        .context("Failed to get file name")?
+        .to_string_lossy()
+        .to_string();
+    let gitignore = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."))
+        .join(".gitignore");
+
+    let existing = fs::read_to_string(&gitignore).unwrap_or_default();
+    let already_ignored = existing.lines().any(|l| {
+        let l = l.trim();
+        l == name || l == format!("/{}", name)
+    });
+    if already_ignored {
+        return Ok(false);
+    }
+
+    let mut contents = existing;
+    if !contents.is_empty() && !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    contents.push_str(&name);
+    contents.push('\n');
+
+    fs::write(&gitignore, contents).context("Failed to write .gitignore")?;
+
+    Ok(true)
+}
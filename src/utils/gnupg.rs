@@ -0,0 +1,263 @@
+// Reading keys out of a GnuPG home directory
+//
+// Public keys are read from the keyring directly. GnuPG 2.1+ keeps secret keys in its
+// agent's own format, so those are exported with `gpg` unless there is a legacy secring.gpg
+
+use super::vecu8::ToHex;
+use anyhow::{bail, Context, Result};
+use pgp::{
+    packet::PacketParser,
+    types::{KeyTrait, Tag},
+    Deserializable, SignedPublicKey, SignedSecretKey,
+};
+use std::{
+    fs,
+    io::Cursor,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+/// Blob holding an OpenPGP keyblock in a keybox file
+const KEYBOX_OPENPGP_BLOB: u8 = 2;
+
+/// `--homedir`, then `GNUPGHOME`, then ~/.gnupg
+pub fn home_dir(homedir: Option<PathBuf>) -> Result<PathBuf> {
+    let dir = match homedir {
+        Some(dir) => dir,
+        None => match std::env::var("GNUPGHOME") {
+            Ok(dir) if !dir.trim().is_empty() => dir.into(),
+            _ => home::home_dir()
+                .context("Failed to get home directory")?
+                .join(".gnupg"),
+        },
+    };
+
+    if !dir.is_dir() {
+        bail!("GnuPG home directory {} doesn't exist", dir.display());
+    }
+    Ok(dir)
+}
+
+/// Every public key of the keyring, pubring.kbx or the legacy pubring.gpg
+pub fn public_keys(home: &Path) -> Result<Vec<SignedPublicKey>> {
+    let keybox = home.join("pubring.kbx");
+    if keybox.is_file() {
+        let data = fs::read(&keybox).context("Failed to read pubring.kbx")?;
+        let mut keys = vec![];
+        for (index, block) in keybox_keyblocks(&data) {
+            let name = format!("pubring.kbx (blob {})", index);
+            keys.extend(parse_keyring::<SignedPublicKey>(block, &name));
+        }
+        return Ok(keys);
+    }
+
+    let legacy = home.join("pubring.gpg");
+    if legacy.is_file() {
+        let data = fs::read(&legacy).context("Failed to read pubring.gpg")?;
+        return Ok(parse_keyring(&data, "pubring.gpg"));
+    }
+
+    bail!("No public keyring found in {}", home.display())
+}
+
+/// Keys of a binary keyring
+///
+/// Keyrings have trust packets after the signatures, which pgp doesn't expect and drops
+/// the subkeys they follow for. Packets and keys pgp can't parse, e.g. of an unsupported
+/// algorithm, are skipped with a warning so that they don't keep the others from importing
+fn parse_keyring<T: Deserializable>(data: &[u8], name: &str) -> Vec<T> {
+    let packets = PacketParser::new(Cursor::new(data))
+        .enumerate()
+        .filter_map(|(i, p)| match p {
+            Ok(p) => Some(p),
+            Err(e) => {
+                eprintln!("Skipping malformed packet {} of {}: {}", i, name, e);
+                None
+            }
+        })
+        .filter(|p| p.tag() != Tag::Trust);
+
+    T::from_packets(packets.peekable())
+        .filter_map(|k| match k {
+            Ok(k) => Some(k),
+            Err(e) => {
+                eprintln!("Skipping a key of {}: {}", name, e);
+                None
+            }
+        })
+        .collect()
+}
+
+/// The OpenPGP keyblocks of a keybox file, with the index of their blob
+///
+/// Every blob starts with its length and type, OpenPGP blobs then give the offset and
+/// length of their keyblock
+fn keybox_keyblocks(data: &[u8]) -> Vec<(usize, &[u8])> {
+    // Offsets come from the file, a corrupt one must not overflow
+    let be_u32 = |at: usize| -> Option<usize> {
+        let bytes = data.get(at..at.checked_add(4)?)?;
+        Some(u32::from_be_bytes(bytes.try_into().ok()?) as usize)
+    };
+    let keyblock = |pos: usize| -> Option<&[u8]> {
+        let start = pos.checked_add(be_u32(pos + 8)?)?;
+        let end = start.checked_add(be_u32(pos + 12)?)?;
+        data.get(start..end)
+    };
+
+    let mut blocks = vec![];
+    let mut pos = 0;
+    let mut index = 0;
+    while let Some(len) = be_u32(pos).filter(|len| *len >= 5) {
+        if data.get(pos + 4) == Some(&KEYBOX_OPENPGP_BLOB) {
+            if let Some(block) = keyblock(pos) {
+                blocks.push((index, block));
+            }
+        }
+        let Some(next) = pos.checked_add(len) else {
+            break;
+        };
+        pos = next;
+        index += 1;
+    }
+    blocks
+}
+
+/// The secret key of a fingerprint, `None` if the keyring doesn't have it
+///
+/// `gpg` may ask for the passphrase to export it
+pub fn secret_key(home: &Path, fingerprint: &str) -> Result<Option<SignedSecretKey>> {
+    let legacy = home.join("secring.gpg");
+    if legacy.is_file() {
+        let data = fs::read(&legacy).context("Failed to read secring.gpg")?;
+        for key in parse_keyring::<SignedSecretKey>(&data, "secring.gpg") {
+            if key.fingerprint().to_hex().eq_ignore_ascii_case(fingerprint) {
+                return Ok(Some(key));
+            }
+        }
+    }
+
+    let output = Command::new("gpg")
+        .arg("--homedir")
+        .arg(home)
+        .args(["--armor", "--export-secret-keys", fingerprint])
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::inherit())
+        .output()
+        .context(
+            "Failed to run gpg to export the secret key, export it with \
+            `gpg --armor --export-secret-keys` and use `envx import secret`",
+        )?;
+    if !output.status.success() {
+        bail!("gpg failed to export the secret key of {}", fingerprint);
+    }
+
+    let armored = String::from_utf8(output.stdout).context("gpg exported an invalid key")?;
+    if armored.trim().is_empty() {
+        return Ok(None);
+    }
+    let (key, _) = SignedSecretKey::from_string(&armored)
+        .context("Failed to parse the key exported by gpg")?;
+    Ok(Some(key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pgp::{packet::Packet, ser::Serialize};
+
+    /// Ed25519 key with a Curve25519 encryption subkey, exported from GnuPG
+    const PUBLIC_KEY: &str = r#"-----BEGIN PGP PUBLIC KEY BLOCK-----
+
+mDMEatRW4BYJKwYBBAHaRw8BAQdADsuN1crBJlwP5MksCjAeY4PVBdPqxxPreQFb
+cWW6wuu0F1Rlc3QgPHRlc3RAZXhhbXBsZS5jb20+iJAEExYIADgWIQTFJeIE3EyE
+uWXGdhR2+1jWwiQlngUCatRW4AIbAwULCQgHAgYVCgkICwIEFgIDAQIeAQIXgAAK
+CRB2+1jWwiQlntaRAQDkfrScBb2sbTDsZofBs2q7/+l/x9CNoQoW9I+wbcr4NgD/
+ReG4rssfH6AmjFPrqUDbQwMGBxjA0eCoN3QKUzuEygu4OARq1FbgEgorBgEEAZdV
+AQUBAQdALd7pHov9dm+lKB/9ysuUssjdC4UCJpC2MZNO7lMBoTsDAQgHiHgEGBYI
+ACAWIQTFJeIE3EyEuWXGdhR2+1jWwiQlngUCatRW4AIbDAAKCRB2+1jWwiQlnhGK
+AQDhqx2UTj2noPkE191/q/i4vRFIclyUMKu8abvgFvTBSgD+LzzCyxq67InHQawD
+hwc3KYIZv5JkH8aKFzmuZYmm6Ac=
+=Mk++
+-----END PGP PUBLIC KEY BLOCK-----
+"#;
+
+    /// Trust packet, as GnuPG writes after the signatures of a keyring
+    const TRUST: [u8; 4] = [0xcc, 0x02, 0x00, 0x00];
+
+    fn public_key() -> SignedPublicKey {
+        SignedPublicKey::from_string(PUBLIC_KEY).unwrap().0
+    }
+
+    /// A keybox blob: length, type, version, flags, then the offset and length of its data
+    fn blob(kind: u8, data: &[u8]) -> Vec<u8> {
+        let mut blob = ((16 + data.len()) as u32).to_be_bytes().to_vec();
+        blob.extend([kind, 1, 0, 0]);
+        blob.extend(16u32.to_be_bytes());
+        blob.extend((data.len() as u32).to_be_bytes());
+        blob.extend(data);
+        blob
+    }
+
+    /// The packets of a key, with a trust packet after every signature
+    fn with_trust_packets(key: &SignedPublicKey) -> Vec<u8> {
+        let mut keyring = vec![];
+        for packet in PacketParser::new(Cursor::new(key.to_bytes().unwrap())) {
+            let packet = packet.unwrap();
+            keyring.extend(packet.to_bytes().unwrap());
+            if matches!(packet, Packet::Signature(_)) {
+                keyring.extend(TRUST);
+            }
+        }
+        keyring
+    }
+
+    #[test]
+    fn reads_the_openpgp_blobs_of_a_keybox() {
+        let key = public_key();
+        let keyblock = key.to_bytes().unwrap();
+
+        // The header blob comes first, it isn't a keyblock
+        let mut keybox = blob(1, &[0; 16]);
+        keybox.extend(blob(KEYBOX_OPENPGP_BLOB, &keyblock));
+
+        let blocks = keybox_keyblocks(&keybox);
+        assert_eq!(blocks, [(1, keyblock.as_slice())]);
+
+        let keys = parse_keyring::<SignedPublicKey>(blocks[0].1, "pubring.kbx");
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].fingerprint(), key.fingerprint());
+    }
+
+    #[test]
+    fn skips_truncated_blobs() {
+        let keyblock = public_key().to_bytes().unwrap();
+        let valid = blob(KEYBOX_OPENPGP_BLOB, &keyblock);
+
+        // Cut in the middle of the keyblock
+        let truncated = &valid[..valid.len() - 10];
+        assert!(keybox_keyblocks(truncated).is_empty());
+
+        // An offset and a length that overflow
+        let mut corrupt = valid.clone();
+        corrupt[8..12].copy_from_slice(&u32::MAX.to_be_bytes());
+        corrupt[12..16].copy_from_slice(&u32::MAX.to_be_bytes());
+        corrupt.extend(&valid);
+        assert_eq!(keybox_keyblocks(&corrupt), [(1, keyblock.as_slice())]);
+
+        // A keyblock cut in its first packet yields no key rather than an error
+        assert!(parse_keyring::<SignedPublicKey>(&keyblock[..10], "test").is_empty());
+    }
+
+    #[test]
+    fn ignores_trust_packets() {
+        let key = public_key();
+        let keyring = with_trust_packets(&key);
+        assert!(keyring.windows(TRUST.len()).any(|w| w == TRUST));
+
+        let keys = parse_keyring::<SignedPublicKey>(&keyring, "pubring.gpg");
+        assert_eq!(keys.len(), 1);
+        assert_eq!(keys[0].public_subkeys.len(), 1);
+        assert_eq!(keys[0].fingerprint(), key.fingerprint());
+        keys[0].verify().unwrap();
+    }
+}
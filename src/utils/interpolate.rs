@@ -0,0 +1,258 @@
+use anyhow::{bail, Context, Result};
+use std::collections::BTreeMap;
+
+/// Expand `${NAME}` and `${NAME:-default}` references between variables
+///
+/// Names that aren't variables of the project are read from the environment of envx,
+/// e.g. `${HOME}`. The default is used when NAME is missing or empty, and may itself
+/// contain references. `$${NAME}` is left as a literal `${NAME}`, any other `$` is kept
+/// as is.
+pub fn interpolate(variables: &BTreeMap<String, String>) -> Result<BTreeMap<String, String>> {
+    interpolate_with(variables, |name| std::env::var(name).ok())
+}
+
+/// `interpolate`, with `env` looking up the names that aren't variables of the project
+fn interpolate_with(
+    variables: &BTreeMap<String, String>,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<BTreeMap<String, String>> {
+    let mut resolver = Resolver {
+        raw: variables,
+        env: &env,
+        resolved: BTreeMap::new(),
+        stack: vec![],
+    };
+
+    for name in variables.keys() {
+        resolver.resolve(name)?;
+    }
+
+    Ok(resolver.resolved)
+}
+
+struct Resolver<'a> {
+    raw: &'a BTreeMap<String, String>,
+    /// Fallback for names that aren't in `raw`
+    env: &'a dyn Fn(&str) -> Option<String>,
+    resolved: BTreeMap<String, String>,
+    /// Variables currently being expanded, used to detect cycles
+    stack: Vec<String>,
+}
+
+impl Resolver<'_> {
+    fn resolve(&mut self, name: &str) -> Result<Option<String>> {
+        if let Some(value) = self.resolved.get(name) {
+            return Ok(Some(value.clone()));
+        }
+        let Some(raw) = self.raw.get(name) else {
+            return Ok((self.env)(name));
+        };
+
+        if let Some(start) = self.stack.iter().position(|n| n == name) {
+            let mut cycle = self.stack[start..].to_vec();
+            cycle.push(name.to_string());
+            bail!("Circular reference: {}", cycle.join(" -> "));
+        }
+
+        self.stack.push(name.to_string());
+        let value = self.expand(raw);
+        self.stack.pop();
+
+        let value = value?;
+        self.resolved.insert(name.to_string(), value.clone());
+        Ok(Some(value))
+    }
+
+    /// Name of the variable being expanded
+    fn current(&self) -> &str {
+        self.stack.last().map(String::as_str).unwrap_or_default()
+    }
+
+    fn expand(&mut self, value: &str) -> Result<String> {
+        let mut expanded = String::with_capacity(value.len());
+        let mut rest = value;
+
+        while let Some(i) = rest.find('$') {
+            expanded.push_str(&rest[..i]);
+            rest = &rest[i..];
+
+            if rest.starts_with("$${") {
+                expanded.push_str("${");
+                rest = &rest[3..];
+                continue;
+            }
+            if !rest.starts_with("${") {
+                expanded.push('$');
+                rest = &rest[1..];
+                continue;
+            }
+
+            let end = closing_brace(&rest[2..])
+                .with_context(|| format!("Unterminated reference in {}", self.current()))?;
+            let reference = &rest[2..2 + end];
+            rest = &rest[2 + end + 1..];
+
+            let (name, default) = match reference.split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (reference, None),
+            };
+            if !is_valid_name(name) {
+                bail!("Invalid reference ${{{}}} in {}", reference, self.current());
+            }
+
+            let resolved = match (self.resolve(name)?, default) {
+                (Some(v), Some(default)) if v.is_empty() => self.expand(default)?,
+                (Some(v), _) => v,
+                (None, Some(default)) => self.expand(default)?,
+                (None, None) => bail!(
+                    "Unresolved reference ${{{}}} in {}, set it in the project or the environment, or provide a default with ${{{}:-default}}",
+                    name,
+                    self.current(),
+                    name
+                ),
+            };
+            expanded.push_str(&resolved);
+        }
+
+        expanded.push_str(rest);
+        Ok(expanded)
+    }
+}
+
+/// Index of the `}` closing a reference, accounting for nested references in defaults
+fn closing_brace(s: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(i),
+            '}' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    /// Only HOME and EMPTY_ENV are set in the environment
+    fn expand(pairs: &[(&str, &str)]) -> Result<BTreeMap<String, String>> {
+        interpolate_with(&variables(pairs), |name| match name {
+            "HOME" => Some("/home/alice".into()),
+            "EMPTY_ENV" => Some("".into()),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn references_between_variables() {
+        let expanded = expand(&[
+            ("HOST", "db"),
+            ("PORT", "5432"),
+            ("URL", "postgres://${HOST}:${PORT}/app"),
+            ("NESTED", "${URL}?ssl=true"),
+        ])
+        .unwrap();
+
+        assert_eq!(expanded["URL"], "postgres://db:5432/app");
+        assert_eq!(expanded["NESTED"], "postgres://db:5432/app?ssl=true");
+    }
+
+    #[test]
+    fn defaults() {
+        let expanded = expand(&[
+            ("EMPTY", ""),
+            ("HOST", "db"),
+            ("MISSING", "${NOPE:-fallback}"),
+            ("EMPTY_DEFAULT", "${EMPTY:-fallback}"),
+            ("SET", "${HOST:-fallback}"),
+            ("NESTED", "${NOPE:-${HOST}:${PORT:-5432}}"),
+            ("BLANK", "${NOPE:-}"),
+            ("EMPTY_ENV_DEFAULT", "${EMPTY_ENV:-fallback}"),
+        ])
+        .unwrap();
+
+        assert_eq!(expanded["MISSING"], "fallback");
+        assert_eq!(expanded["EMPTY_DEFAULT"], "fallback");
+        assert_eq!(expanded["SET"], "db");
+        assert_eq!(expanded["NESTED"], "db:5432");
+        assert_eq!(expanded["BLANK"], "");
+        assert_eq!(expanded["EMPTY_ENV_DEFAULT"], "fallback");
+    }
+
+    #[test]
+    fn escapes_and_lone_dollars() {
+        let expanded = expand(&[
+            ("HOST", "db"),
+            ("ESCAPED", "$${HOST} is ${HOST}"),
+            ("LONE", "costs $5, $HOST and $"),
+        ])
+        .unwrap();
+
+        assert_eq!(expanded["ESCAPED"], "${HOST} is db");
+        assert_eq!(expanded["LONE"], "costs $5, $HOST and $");
+    }
+
+    #[test]
+    fn environment_fallback() {
+        let expanded = expand(&[
+            ("CACHE", "${HOME}/.cache"),
+            // Project variables win over the environment
+            ("HOME", "/srv/app"),
+        ])
+        .unwrap();
+        assert_eq!(expanded["CACHE"], "/srv/app/.cache");
+
+        let expanded = expand(&[("CACHE", "${HOME}/.cache")]).unwrap();
+        assert_eq!(expanded["CACHE"], "/home/alice/.cache");
+        assert!(!expanded.contains_key("HOME"));
+    }
+
+    #[test]
+    fn unresolved_references() {
+        let err = expand(&[("URL", "${HOST}/app")]).unwrap_err().to_string();
+        assert!(
+            err.starts_with("Unresolved reference ${HOST} in URL"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn cycles() {
+        let err = expand(&[("A", "${B}"), ("B", "${C}"), ("C", "${A}")])
+            .unwrap_err()
+            .to_string();
+        assert_eq!(err, "Circular reference: A -> B -> C -> A");
+
+        let err = expand(&[("SELF", "x${SELF}")]).unwrap_err().to_string();
+        assert_eq!(err, "Circular reference: SELF -> SELF");
+
+        // A default isn't used to break a cycle
+        assert!(expand(&[("A", "${B:-x}"), ("B", "${A}")]).is_err());
+    }
+
+    #[test]
+    fn malformed_references() {
+        assert!(expand(&[("A", "${UNTERMINATED")]).is_err());
+        assert!(expand(&[("A", "${1ST}")]).is_err());
+        assert!(expand(&[("A", "${}")]).is_err());
+    }
+}
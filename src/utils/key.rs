@@ -24,6 +24,11 @@ impl Key {
         Ok(key)
     }
 
+    /// Imported public keys have no `private.key` in the vault
+    pub fn is_pubkey_only(&self) -> bool {
+        self.pubkey_only == Some(true)
+    }
+
     pub fn secret_key(&self) -> Result<String> {
         let key_location = get_vault_location()?
             .join(self.fingerprint.clone())
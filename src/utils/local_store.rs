@@ -0,0 +1,324 @@
+// local store path = ~/.config/envcli/store/<project_id>.json
+
+use super::{config::get_config, rpgp::decrypt_full_many};
+use anyhow::{Context, Result};
+use chrono::Utc;
+use envx::{
+    crypto::encrypt_multi,
+    file::write_atomic,
+    kvpair::KVPair,
+    partial_variable::{DeDupe, ParsedPartialVariable, PartialVariable, ToKVPair, ToParsed},
+};
+use pgp::{Deserializable, SignedPublicKey};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+use std::{fs, path::PathBuf};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SyncState {
+    /// Identical to a variable on the server
+    Synced,
+    /// Created offline, not uploaded yet
+    Pending,
+    /// Deleted offline, still on the server
+    Deleted,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LocalVariable {
+    pub id: String,
+    /// Armored PGP message containing a serialized `KVPair`
+    pub value: String,
+    pub project_id: String,
+    pub created_at: String,
+    pub state: SyncState,
+    /// Changed on the server as well, kept until resolved with `envx sync --prefer`
+    #[serde(default)]
+    pub conflict: bool,
+}
+
+impl LocalVariable {
+    pub fn to_partial(&self) -> PartialVariable {
+        PartialVariable {
+            id: self.id.clone(),
+            value: self.value.clone(),
+            project_id: self.project_id.clone(),
+            created_at: self.created_at.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LocalProject {
+    pub project_id: String,
+    /// Armored public keys of the project members, as of the last sync
+    pub recipients: Vec<String>,
+    /// When the project was last synced with the server
+    pub last_sync: Option<String>,
+    pub variables: Vec<LocalVariable>,
+    /// Scoped keys of the variables that should get a new value, e.g. after a member was
+    /// removed. Only kept on this machine, other members don't see them
+    #[serde(default)]
+    pub rotate: Vec<String>,
+}
+
+impl LocalProject {
+    pub fn new(project_id: &str) -> Self {
+        Self {
+            project_id: project_id.to_string(),
+            recipients: vec![],
+            last_sync: None,
+            variables: vec![],
+            rotate: vec![],
+        }
+    }
+
+    /// Read a project from the local store, or an empty one if it was never stored
+    pub fn read(project_id: &str) -> Result<Self> {
+        let path = get_store_location()?.join(format!("{}.json", project_id));
+        if !path.exists() {
+            return Ok(Self::new(project_id));
+        }
+
+        let contents = fs::read_to_string(path).context("Failed to read local project")?;
+        serde_json::from_str::<LocalProject>(&contents).context("Failed to parse local project")
+    }
+
+    pub fn write(&self) -> Result<()> {
+        let location = get_store_location()?;
+        fs::create_dir_all(&location).context("Failed to create local store directory")?;
+
+        let contents = serde_json::to_string_pretty(self)
+            .context("Failed to serialize local project to JSON string")?;
+        // Holds changes not synced yet, a crash mid-write must not lose them
+        write_atomic(
+            &location.join(format!("{}.json", self.project_id)),
+            &contents,
+        )
+        .context("Failed to write local project")?;
+
+        Ok(())
+    }
+
+    /// All projects in the local store
+    pub fn list() -> Result<Vec<Self>> {
+        let location = get_store_location()?;
+        if !location.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut projects = vec![];
+        for entry in fs::read_dir(location).context("Failed to read local store")? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == "json") {
+                let contents = fs::read_to_string(&path).context("Failed to read local project")?;
+                projects.push(
+                    serde_json::from_str::<LocalProject>(&contents)
+                        .context("Failed to parse local project")?,
+                );
+            }
+        }
+
+        Ok(projects)
+    }
+
+    /// Flag variables for rotation, by scoped key
+    pub fn flag_for_rotation(project_id: &str, keys: &[String]) -> Result<()> {
+        let mut project = Self::read(project_id)?;
+        for key in keys {
+            if !project.rotate.contains(key) {
+                project.rotate.push(key.clone());
+            }
+        }
+        project.rotate.sort();
+        project.write()
+    }
+
+    /// Clear the rotation flag of variables that got a new value
+    pub fn clear_rotation(project_id: &str, keys: &[String]) -> Result<()> {
+        let mut project = Self::read(project_id)?;
+        if !project.rotate.iter().any(|r| keys.contains(r)) {
+            return Ok(());
+        }
+        project.rotate.retain(|r| !keys.contains(r));
+        project.write()
+    }
+
+    /// Delete every version of the key of a variable, `kvpairs` are the decrypted `variables`
+    ///
+    /// Versions that were never uploaded are dropped, the others are deleted from the server
+    /// on the next sync. Only deleting one would bring an older version back
+    pub fn delete(&mut self, variable_id: &str, kvpairs: &[KVPair]) -> Result<()> {
+        let name = self
+            .variables
+            .iter()
+            .zip(kvpairs)
+            .find(|(v, _)| v.id == variable_id)
+            .map(|(_, k)| k.scoped_key())
+            .context("Variable not found in the local store")?;
+
+        let versions = self
+            .variables
+            .iter()
+            .zip(kvpairs)
+            .filter(|(_, k)| k.scoped_key() == name)
+            .map(|(v, _)| v.id.clone())
+            .collect::<Vec<String>>();
+
+        self.variables
+            .retain(|v| !(v.state == SyncState::Pending && versions.contains(&v.id)));
+        for variable in self.variables.iter_mut() {
+            if versions.contains(&variable.id) {
+                variable.state = SyncState::Deleted;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Variables that are visible to the user, ie. not deleted offline
+    pub fn visible(&self) -> Vec<&LocalVariable> {
+        self.variables
+            .iter()
+            .filter(|v| v.state != SyncState::Deleted)
+            .collect()
+    }
+}
+
+/// Get the local store location ~/.config/envcli/store, see `resolve::config_dir`
+pub fn get_store_location() -> Result<PathBuf> {
+    let path = super::resolve::config_dir()?.value.join("store");
+
+    Ok(path)
+}
+
+pub fn generate_local_id() -> String {
+    format!("local-{}", hex::encode(rand::random::<[u8; 16]>()))
+}
+
+/// Decrypt a list of local variables, keeping the order
+pub fn decrypt_local(variables: &[&LocalVariable]) -> Result<Vec<KVPair>> {
+    let decrypted = decrypt_full_many(
+        variables.iter().map(|v| v.value.clone()).collect(),
+        &get_config()?,
+    )?;
+
+    Ok(decrypted
+        .iter()
+        .map(|d| KVPair::from_json(d))
+        .collect::<Result<Vec<KVPair>, envx::error::Error>>()?)
+}
+
+/// Mirror of the variable related `SDK` methods, backed by the local store
+///
+/// Used when `Config.online` is false
+pub struct LocalStore {}
+impl LocalStore {
+    pub fn set_many(
+        kvpairs: Vec<KVPair>,
+        partial_fingerprint: &str,
+        project_id: &str,
+    ) -> Result<Vec<String>> {
+        let mut project = LocalProject::read(project_id)?;
+
+        // Without a previous sync we only know about our own key
+        let recipients = if project.recipients.is_empty() {
+            let key = get_config()?.get_key(partial_fingerprint)?;
+            vec![key.public_key()?]
+        } else {
+            project.recipients.clone()
+        };
+
+        let pubkeys = recipients
+            .iter()
+            .map(|k| Ok(SignedPublicKey::from_string(k)?.0))
+            .collect::<Result<Vec<SignedPublicKey>>>()?;
+
+        let messages = kvpairs
+            .par_iter()
+            .map(|k| encrypt_multi(&k.to_json()?, &pubkeys))
+            .collect::<Result<Vec<String>, envx::error::Error>>()?;
+
+        let created_at = Utc::now().to_rfc3339();
+        let ids = messages
+            .into_iter()
+            .map(|value| {
+                let variable = LocalVariable {
+                    id: generate_local_id(),
+                    value,
+                    project_id: project_id.to_string(),
+                    created_at: created_at.clone(),
+                    state: SyncState::Pending,
+                    conflict: false,
+                };
+                let id = variable.id.clone();
+                project.variables.push(variable);
+                id
+            })
+            .collect::<Vec<String>>();
+
+        project.write()?;
+
+        Ok(ids)
+    }
+
+    pub fn get_all_variables() -> Result<(Vec<KVPair>, Vec<ParsedPartialVariable>)> {
+        let projects = LocalProject::list()?;
+        let variables = projects
+            .iter()
+            .flat_map(|p| p.visible())
+            .collect::<Vec<&LocalVariable>>();
+
+        let parsed = decrypt_local(&variables)?;
+        let partials = variables
+            .iter()
+            .map(|v| v.to_partial())
+            .collect::<Vec<PartialVariable>>()
+            .zip_to_parsed(parsed.clone());
+
+        Ok((parsed, partials))
+    }
+
+    pub fn get_variables(project_id: &str) -> Result<(Vec<KVPair>, Vec<PartialVariable>)> {
+        let project = LocalProject::read(project_id)?;
+        let variables = project.visible();
+
+        let parsed = decrypt_local(&variables)?;
+        let partials = variables
+            .iter()
+            .zip(parsed.iter())
+            .map(|(v, k)| {
+                Ok(PartialVariable {
+                    value: k.to_json()?,
+                    ..v.to_partial()
+                })
+            })
+            .collect::<Result<Vec<PartialVariable>>>()?;
+
+        Ok((parsed, partials))
+    }
+
+    /// Return variables as a list of kv pairs
+    ///
+    /// Sorted, and pruned of duplicates (by created_at date)
+    pub fn get_variables_pruned(project_id: &str) -> Result<Vec<KVPair>> {
+        let (kvpairs, partial) = Self::get_variables(project_id)?;
+        let mut pruned = partial.zip_to_parsed(kvpairs).dedupe().to_kvpair();
+        pruned.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(pruned)
+    }
+
+    /// Delete every version of the variable's key, see `LocalProject::delete`
+    pub fn delete_variable(variable_id: &str) -> Result<()> {
+        let mut project = LocalProject::list()?
+            .into_iter()
+            .find(|p| p.variables.iter().any(|v| v.id == variable_id))
+            .context("Variable not found in the local store")?;
+
+        let kvpairs = decrypt_local(&project.variables.iter().collect::<Vec<&LocalVariable>>())?;
+        project.delete(variable_id, &kvpairs)?;
+
+        project.write()
+    }
+}
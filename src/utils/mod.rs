@@ -1,13 +1,20 @@
-pub mod auth;
+pub mod backup;
 pub mod btreemap;
 pub mod choice;
 pub mod config;
+pub mod dotenv;
+pub mod file;
+pub mod gnupg;
+pub mod interpolate;
 pub mod key;
 pub mod keyring;
-pub mod kvpair;
-pub mod partial_variable;
+pub mod local_store;
+pub mod project_file;
 pub mod prompt;
+pub mod resolve;
 pub mod rpgp;
 pub mod settings;
+pub mod shamir;
 pub mod table;
+pub mod template;
 pub mod vecu8;
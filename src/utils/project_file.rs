@@ -0,0 +1,111 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+pub const PROJECT_FILE: &str = ".envx.toml";
+
+/// `.envx.toml`, committed at the root of a repository so every checkout is linked
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProjectFile {
+    pub project_id: String,
+    /// Default environment for the repository
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub environment: Option<String>,
+    /// Keys that must be set for the project to run, uppercase like every variable key
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub required: Vec<String>,
+}
+
+impl ProjectFile {
+    /// The nearest `.envx.toml` in the current directory or its parents
+    pub fn find() -> Result<Option<(Self, PathBuf)>> {
+        let mut dir = std::env::current_dir()?;
+        loop {
+            let path = dir.join(PROJECT_FILE);
+            if path.is_file() {
+                let contents = fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                let file = toml::from_str::<Self>(&contents)
+                    .with_context(|| format!("Failed to parse {}", path.display()))?;
+                return Ok(Some((file, path)));
+            }
+            if !dir.pop() {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// The nearest `.envx.toml`, only if it belongs to `project_id`
+    pub fn find_for(project_id: &str) -> Result<Option<Self>> {
+        Ok(Self::find()?
+            .map(|(file, _)| file)
+            .filter(|f| f.project_id == project_id))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let contents = toml::to_string(self).context("Failed to serialize project file")?;
+        fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Required keys that are missing or empty
+    ///
+    /// Keys are compared uppercase, in case the file was edited by hand
+    pub fn missing(&self, variables: &BTreeMap<String, String>) -> Vec<String> {
+        self.required
+            .iter()
+            .map(|k| k.to_uppercase())
+            .filter(|k| variables.get(k).map_or(true, |v| v.is_empty()))
+            .collect()
+    }
+}
+
+/// Fail if the `.envx.toml` of the project lists keys that aren't set
+pub fn ensure_required(project_id: &str, variables: &BTreeMap<String, String>) -> Result<()> {
+    let Some(file) = ProjectFile::find_for(project_id)? else {
+        return Ok(());
+    };
+
+    let missing = file.missing(variables);
+    if !missing.is_empty() {
+        anyhow::bail!(
+            "Missing required variable(s) listed in {}: {}",
+            PROJECT_FILE,
+            missing.join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Where `link --file` writes `.envx.toml`: the root of the git repository, or the current directory
+pub fn project_file_location() -> Result<PathBuf> {
+    let cwd = std::env::current_dir()?;
+    let root = cwd
+        .ancestors()
+        .find(|d| d.join(".git").exists())
+        .unwrap_or(&cwd);
+    Ok(root.join(PROJECT_FILE))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn required_keys_are_uppercase() {
+        let file = ProjectFile {
+            project_id: "project".into(),
+            environment: None,
+            required: vec!["database_url".into(), "API_KEY".into(), "Empty".into()],
+        };
+        let variables = BTreeMap::from([
+            ("DATABASE_URL".to_string(), "postgres://".to_string()),
+            ("EMPTY".to_string(), "".to_string()),
+        ]);
+
+        assert_eq!(file.missing(&variables), ["API_KEY", "EMPTY"]);
+    }
+}
@@ -0,0 +1,394 @@
+// Effective settings, each one from the first layer that sets it:
+// flags, then ENVX_* environment variables, then files, then defaults
+
+use super::{
+    config::{Config, Project, DEFAULT_PROFILE},
+    project_file::ProjectFile,
+};
+use crate::constants::DEFAULT_API_URL;
+use anyhow::Result;
+use envx::paths::{ConfigDir, ENV_CONFIG_DIR};
+use std::{
+    fmt::Display,
+    path::PathBuf,
+    sync::{Once, OnceLock},
+};
+pub const ENV_PROFILE: &str = "ENVX_PROFILE";
+pub const ENV_API_URL: &str = "ENVX_API_URL";
+pub const ENV_KEY: &str = "ENVX_KEY";
+pub const ENV_PROJECT: &str = "ENVX_PROJECT";
+
+/// Set by the global `--profile` flag
+static PROFILE: OnceLock<String> = OnceLock::new();
+
+/// Where an effective value came from
+#[derive(Debug, Clone)]
+pub enum Source {
+    Default,
+    Flag(&'static str),
+    Env(&'static str),
+    ConfigFile,
+    Profile(String),
+    ProjectFile(PathBuf),
+    LinkedDirectory(PathBuf),
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::Flag(flag) => write!(f, "flag {}", flag),
+            Source::Env(name) => write!(f, "environment variable {}", name),
+            Source::ConfigFile => write!(f, "config file"),
+            Source::Profile(name) => write!(f, "profile {}", name),
+            Source::ProjectFile(path) => write!(f, "{}", path.display()),
+            Source::LinkedDirectory(path) => write!(f, "directory linked at {}", path.display()),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Resolved<T> {
+    pub value: T,
+    pub source: Source,
+}
+
+impl<T> Resolved<T> {
+    fn new(value: T, source: Source) -> Self {
+        Self { value, source }
+    }
+}
+
+/// Looks up environment variables, tests pass their own
+type Vars<'a> = &'a dyn Fn(&str) -> Option<String>;
+
+fn system_vars(name: &str) -> Option<String> {
+    std::env::var(name).ok()
+}
+
+/// A non-empty environment variable
+fn var(vars: Vars, name: &str) -> Option<String> {
+    vars(name).filter(|v| !v.trim().is_empty())
+}
+
+/// `ENVX_CONFIG_DIR`, then `$XDG_CONFIG_HOME/envcli`, then ~/.config/envcli
+///
+/// An existing ~/.config/envcli stays in use when `XDG_CONFIG_HOME` is set, until
+/// `envx config migrate` moves it
+pub fn config_dir() -> Result<Resolved<PathBuf>> {
+    let dir = envx::paths::config_dir()?;
+    if let ConfigDir::Legacy { path, xdg } = &dir {
+        static NOTICE: Once = Once::new();
+        NOTICE.call_once(|| {
+            eprintln!(
+                "XDG_CONFIG_HOME is set but {} is still in use, run `envx config migrate` to move it to {}",
+                path.display(),
+                xdg.display()
+            );
+        });
+    }
+
+    Ok(resolved_config_dir(dir))
+}
+
+fn resolved_config_dir(dir: ConfigDir) -> Resolved<PathBuf> {
+    match dir {
+        ConfigDir::Env(path) => Resolved::new(path, Source::Env(ENV_CONFIG_DIR)),
+        ConfigDir::Xdg(path) => Resolved::new(path, Source::Env("XDG_CONFIG_HOME")),
+        ConfigDir::Default(path) | ConfigDir::Legacy { path, .. } => {
+            Resolved::new(path, Source::Default)
+        }
+    }
+}
+
+/// Select the profile for the rest of the process
+pub fn set_profile(name: String) {
+    let _ = PROFILE.set(name);
+}
+
+/// `--profile`, then `ENVX_PROFILE`, then the default profile
+pub fn profile() -> Resolved<String> {
+    profile_from(PROFILE.get(), &system_vars)
+}
+
+fn profile_from(flag: Option<&String>, vars: Vars) -> Resolved<String> {
+    if let Some(name) = flag.filter(|p| !p.trim().is_empty()) {
+        return Resolved::new(name.clone(), Source::Flag("--profile"));
+    }
+    if let Some(name) = var(vars, ENV_PROFILE) {
+        return Resolved::new(name, Source::Env(ENV_PROFILE));
+    }
+    Resolved::new(DEFAULT_PROFILE.into(), Source::Default)
+}
+
+pub fn active_profile() -> String {
+    profile().value
+}
+
+/// `DEV_MODE`, then `ENVX_API_URL`, then the profile or `sdk_url`
+pub fn api_url(config: &Config) -> Result<Resolved<String>> {
+    api_url_from(config, &system_vars)
+}
+
+fn api_url_from(config: &Config, vars: Vars) -> Result<Resolved<String>> {
+    if vars("DEV_MODE").is_some() {
+        return Ok(Resolved::new(
+            "http://localhost:3000".into(),
+            Source::Env("DEV_MODE"),
+        ));
+    }
+    if let Some(url) = var(vars, ENV_API_URL) {
+        return Ok(Resolved::new(url, Source::Env(ENV_API_URL)));
+    }
+    if let Some(profile) = config.profile()? {
+        return Ok(Resolved::new(
+            profile.url.clone(),
+            Source::Profile(active_profile()),
+        ));
+    }
+
+    Ok(match &config.sdk_url {
+        Some(url) => Resolved::new(url.clone(), Source::ConfigFile),
+        None => Resolved::new(DEFAULT_API_URL.into(), Source::Default),
+    })
+}
+
+/// Partial fingerprint of the key: `-k`, then `ENVX_KEY`, then the default key of the
+/// profile, then the primary key
+pub fn key(config: &Config, flag: Option<String>) -> Result<Resolved<String>> {
+    key_from(config, flag, &system_vars)
+}
+
+fn key_from(config: &Config, flag: Option<String>, vars: Vars) -> Result<Resolved<String>> {
+    if let Some(key) = flag {
+        return Ok(Resolved::new(key, Source::Flag("--key")));
+    }
+    if let Some(key) = var(vars, ENV_KEY) {
+        return Ok(Resolved::new(key, Source::Env(ENV_KEY)));
+    }
+    if let Some(key) = config.profile()?.and_then(|p| p.default_key.clone()) {
+        return Ok(Resolved::new(key, Source::Profile(active_profile())));
+    }
+    Ok(Resolved::new(
+        config.primary_key.clone(),
+        Source::ConfigFile,
+    ))
+}
+
+/// `-p`, then `ENVX_PROJECT`, then `.envx.toml`, then the directories linked in the config
+///
+/// `None` if nothing sets it, commands then ask for it
+pub fn project(config: &Config, flag: Option<String>) -> Result<Option<Resolved<String>>> {
+    if let Some(project_id) = flag {
+        return Ok(Some(Resolved::new(
+            project_id,
+            Source::Flag("--project-id"),
+        )));
+    }
+    if let Some(project_id) = var(&system_vars, ENV_PROJECT) {
+        return Ok(Some(Resolved::new(project_id, Source::Env(ENV_PROJECT))));
+    }
+
+    Ok(links(config)?.into_iter().next().map(|link| match link {
+        Link::File(file, path) => Resolved::new(file.project_id, Source::ProjectFile(path)),
+        Link::Directory(p) => Resolved::new(
+            p.project_id.clone(),
+            Source::LinkedDirectory(p.path.clone()),
+        ),
+    }))
+}
+
+/// A project linked to the current directory or one of its parents
+pub enum Link<'a> {
+    /// `.envx.toml`, and its path
+    File(ProjectFile, PathBuf),
+    /// A directory linked in the config
+    Directory(&'a Project),
+}
+
+impl Link<'_> {
+    pub fn project_id(&self) -> &str {
+        match self {
+            Link::File(file, _) => &file.project_id,
+            Link::Directory(p) => &p.project_id,
+        }
+    }
+
+    pub fn environment(&self) -> Option<&String> {
+        match self {
+            Link::File(file, _) => file.environment.as_ref(),
+            Link::Directory(p) => p.environment.as_ref(),
+        }
+    }
+}
+
+/// The nearest `.envx.toml` and linked directory, the most specific first
+pub fn links(config: &Config) -> Result<Vec<Link<'_>>> {
+    Ok(ordered(ProjectFile::find()?, config.get_project().ok()))
+}
+
+/// Both are the current directory or one of its parents, so the deeper one is more
+/// specific. The file wins when they are in the same directory
+fn ordered(file: Option<(ProjectFile, PathBuf)>, linked: Option<&Project>) -> Vec<Link<'_>> {
+    let linked_first = match (&file, linked) {
+        (Some((_, path)), Some(linked)) => path
+            .parent()
+            .is_some_and(|dir| linked.path != dir && linked.path.starts_with(dir)),
+        _ => false,
+    };
+
+    let file = file.map(|(file, path)| Link::File(file, path));
+    let linked = linked.map(Link::Directory);
+    match linked_first {
+        true => linked.into_iter().chain(file).collect(),
+        false => file.into_iter().chain(linked).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn vars(pairs: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars = pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect::<HashMap<String, String>>();
+        move |name| vars.get(name).cloned()
+    }
+
+    fn config() -> Config {
+        Config {
+            primary_key: "PRIMARY".into(),
+            sdk_url: Some("https://sdk.example.com".into()),
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn profile_flag_then_env_then_default() {
+        let env = vars(&[(ENV_PROFILE, "staging")]);
+        let flag = "work".to_string();
+
+        let resolved = profile_from(Some(&flag), &env);
+        assert_eq!(resolved.value, "work");
+        assert!(matches!(resolved.source, Source::Flag("--profile")));
+
+        let resolved = profile_from(None, &env);
+        assert_eq!(resolved.value, "staging");
+        assert!(matches!(resolved.source, Source::Env(ENV_PROFILE)));
+
+        // Empty values are ignored
+        let resolved = profile_from(Some(&String::new()), &vars(&[(ENV_PROFILE, " ")]));
+        assert_eq!(resolved.value, DEFAULT_PROFILE);
+        assert!(matches!(resolved.source, Source::Default));
+    }
+
+    #[test]
+    fn key_flag_then_env_then_primary_key() {
+        let config = config();
+        let env = vars(&[(ENV_KEY, "ENV")]);
+
+        let resolved = key_from(&config, Some("FLAG".into()), &env).unwrap();
+        assert_eq!(resolved.value, "FLAG");
+        assert!(matches!(resolved.source, Source::Flag("--key")));
+
+        let resolved = key_from(&config, None, &env).unwrap();
+        assert_eq!(resolved.value, "ENV");
+        assert!(matches!(resolved.source, Source::Env(ENV_KEY)));
+
+        let resolved = key_from(&config, None, &vars(&[(ENV_KEY, "")])).unwrap();
+        assert_eq!(resolved.value, "PRIMARY");
+        assert!(matches!(resolved.source, Source::ConfigFile));
+    }
+
+    #[test]
+    fn api_url_dev_mode_then_env_then_config() {
+        let mut config = config();
+
+        let env = vars(&[("DEV_MODE", ""), (ENV_API_URL, "https://env.example.com")]);
+        let resolved = api_url_from(&config, &env).unwrap();
+        assert_eq!(resolved.value, "http://localhost:3000");
+        assert!(matches!(resolved.source, Source::Env("DEV_MODE")));
+
+        let env = vars(&[(ENV_API_URL, "https://env.example.com")]);
+        let resolved = api_url_from(&config, &env).unwrap();
+        assert_eq!(resolved.value, "https://env.example.com");
+        assert!(matches!(resolved.source, Source::Env(ENV_API_URL)));
+
+        let resolved = api_url_from(&config, &vars(&[])).unwrap();
+        assert_eq!(resolved.value, "https://sdk.example.com");
+        assert!(matches!(resolved.source, Source::ConfigFile));
+
+        config.sdk_url = None;
+        let resolved = api_url_from(&config, &vars(&[])).unwrap();
+        assert_eq!(resolved.value, DEFAULT_API_URL);
+        assert!(matches!(resolved.source, Source::Default));
+    }
+
+    #[test]
+    fn config_dir_sources() {
+        let resolved = resolved_config_dir(ConfigDir::Env("/envx".into()));
+        assert!(matches!(resolved.source, Source::Env(ENV_CONFIG_DIR)));
+
+        let resolved = resolved_config_dir(ConfigDir::Xdg("/xdg/envcli".into()));
+        assert!(matches!(resolved.source, Source::Env("XDG_CONFIG_HOME")));
+
+        // The legacy directory is used as is, not the XDG one
+        let resolved = resolved_config_dir(ConfigDir::Legacy {
+            path: "/home/.config/envcli".into(),
+            xdg: "/xdg/envcli".into(),
+        });
+        assert_eq!(resolved.value, PathBuf::from("/home/.config/envcli"));
+        assert!(matches!(resolved.source, Source::Default));
+    }
+
+    fn file(project_id: &str, dir: &str) -> Option<(ProjectFile, PathBuf)> {
+        let file = ProjectFile {
+            project_id: project_id.into(),
+            environment: None,
+            required: vec![],
+        };
+        Some((file, PathBuf::from(dir).join(".envx.toml")))
+    }
+
+    fn linked(project_id: &str, dir: &str) -> Project {
+        Project {
+            project_id: project_id.into(),
+            path: dir.into(),
+            environment: None,
+        }
+    }
+
+    fn ids(links: Vec<Link>) -> Vec<String> {
+        links.iter().map(|l| l.project_id().to_string()).collect()
+    }
+
+    #[test]
+    fn the_most_specific_link_wins() {
+        // A repository with a linked subdirectory
+        let sub = linked("linked", "/repo/service");
+        assert_eq!(
+            ids(ordered(file("file", "/repo"), Some(&sub))),
+            ["linked", "file"]
+        );
+
+        // A repository inside a linked directory
+        let parent = linked("linked", "/work");
+        assert_eq!(
+            ids(ordered(file("file", "/work/repo"), Some(&parent))),
+            ["file", "linked"]
+        );
+
+        // Same directory, the file wins
+        let same = linked("linked", "/repo");
+        assert_eq!(
+            ids(ordered(file("file", "/repo"), Some(&same))),
+            ["file", "linked"]
+        );
+
+        assert_eq!(ids(ordered(None, Some(&sub))), ["linked"]);
+        assert_eq!(ids(ordered(file("file", "/repo"), None)), ["file"]);
+    }
+}
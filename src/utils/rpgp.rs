@@ -1,128 +1,103 @@
 use super::config::{get_config, Config};
+use super::key::Key;
 use super::keyring::try_get_password;
-use anyhow::anyhow;
+use super::vecu8::ToHex as _;
 use anyhow::{Context, Ok, Result};
 use colored::Colorize;
 use crypto_hash::{hex_digest, Algorithm};
+use envx::crypto::{decrypt, KeyPair};
+use envx::file::write_private;
 use hex::ToHex;
-use pgp::composed::message::Message;
-use pgp::{composed, composed::signed_key::*, crypto, types::SecretKeyTrait, Deserializable};
-use rand::prelude::*;
+use pgp::{
+    composed::{self, message::Message, signed_key::*},
+    types::KeyTrait,
+    Deserializable,
+};
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-use smallvec::*;
 use std::{fs, io::Cursor, path::Path};
 
-#[derive(Debug)]
-pub struct KeyPair {
-    pub secret_key: pgp::SignedSecretKey,
-    pub public_key: pgp::SignedPublicKey,
-}
-
-pub(crate) fn get_vault_location() -> anyhow::Result<std::path::PathBuf, anyhow::Error> {
-    let path = home::home_dir()
-        .context("Failed to get home directory")?
-        .join(".config")
-        .join("envcli")
-        .join("keys");
+pub fn get_vault_location() -> anyhow::Result<std::path::PathBuf, anyhow::Error> {
+    let path = crate::utils::resolve::config_dir()?.value.join("keys");
 
     Ok(path)
 }
 
-pub fn generate_key_pair(
-    name: String,
-    email: String,
-    password: String,
-) -> Result<KeyPair, anyhow::Error> {
-    let mut key_params = composed::key::SecretKeyParamsBuilder::default();
-
-    // name email mix, + salt and hash as the primary_user_id
-    key_params
-        // change to 4096 later
-        .key_type(composed::KeyType::Rsa(2048))
-        .can_create_certificates(false)
-        .can_sign(true)
-        .can_encrypt(true)
-        .passphrase(Some(password.clone()))
-        .primary_user_id(generate_hashed_primary_user_id(name.clone(), email.clone()))
-        .preferred_symmetric_algorithms(smallvec![crypto::sym::SymmetricKeyAlgorithm::AES256]);
-
-    let secret_key_params = key_params
-        .build()
-        .expect("Must be able to create secret key params");
-
-    let secret_key = secret_key_params
-        .generate()
-        .expect("Failed to generate a plain key.");
-
-    let passwd_fn = || password.clone();
-
-    let signed_secret_key = secret_key
-        .sign(passwd_fn)
-        .expect("Secret Key must be able to sign its own metadata");
-
-    let public_key = signed_secret_key.public_key();
-    let signed_public_key = public_key
-        .sign(&signed_secret_key, passwd_fn)
-        .expect("Public key must be able to sign its own metadata");
-
-    let key_pair = KeyPair {
-        secret_key: signed_secret_key,
-        public_key: signed_public_key,
-    };
-
-    Ok(key_pair)
+/// Write a key pair to the vault, returns the entry to add to the config
+pub fn save_key_pair(key_pair: &KeyPair, name: &str, email: &str) -> Result<Key> {
+    let fingerprint = key_pair.secret_key.fingerprint().to_hex();
+    let key_dir = get_vault_location()?.join(&fingerprint);
+    fs::create_dir_all(&key_dir).context("Failed to create key directory")?;
+
+    let priv_key = key_pair
+        .secret_key
+        .to_armored_string(None)
+        .context("Failed to armor the private key")?;
+    let pub_key = key_pair
+        .public_key
+        .to_armored_string(None)
+        .context("Failed to armor the public key")?;
+    write_private(&key_dir.join("private.key"), &priv_key)
+        .context("Failed to write private key")?;
+    fs::write(key_dir.join("public.key"), pub_key).context("Failed to write public key")?;
+
+    Ok(Key {
+        fingerprint,
+        note: "".to_string(),
+        primary_user_id: format!("{} <{}>", name, email),
+        hashed_note: generate_hashed_primary_user_id(name.into(), email.into()),
+        pubkey_only: None,
+        uuid: None,
+    })
 }
 
-pub fn encrypt(msg: &str, pubkey_str: &str) -> Result<String, anyhow::Error> {
-    let (pubkey, _) = SignedPublicKey::from_string(pubkey_str)?;
-    // Requires a file name as the first arg, in this case I pass "none", as it's not used
-    let msg = composed::message::Message::new_literal("none", msg);
-
-    let mut rng = StdRng::from_entropy();
-    let new_msg = msg.encrypt_to_keys(
-        &mut rng,
-        crypto::sym::SymmetricKeyAlgorithm::AES128,
-        &[&pubkey],
-    )?;
-
-    Ok(new_msg.to_armored_string(None)?)
-}
-
-pub fn encrypt_multi(msg: &str, pubkeys: &[SignedPublicKey]) -> Result<String, anyhow::Error> {
-    let mut rng = StdRng::from_entropy();
-
-    let borrowed_keys = pubkeys.iter().collect::<SmallVec<[&SignedPublicKey; 1]>>();
-
-    let msg = composed::message::Message::new_literal("none", msg);
-
-    let new_msg = msg.encrypt_to_keys(
-        &mut rng,
-        crypto::sym::SymmetricKeyAlgorithm::AES128,
-        &borrowed_keys,
-    )?;
-
-    Ok(new_msg.to_armored_string(None)?)
-}
+/// Write a key to the vault and register it in the config
+///
+/// `owner` gives the primary user id and hashed note of a key that isn't in the config yet.
+/// Does not write the config. Call it inside `Config::update` to write to disk
+pub fn import_key(
+    config: &mut Config,
+    pubkey: &SignedPublicKey,
+    seckey: Option<&SignedSecretKey>,
+    owner: impl FnOnce() -> Result<(String, String)>,
+) -> Result<()> {
+    let fingerprint = pubkey.fingerprint().to_hex();
+    let key_dir = get_vault_location()?.join(&fingerprint);
+    fs::create_dir_all(&key_dir).context("Failed to create key directory")?;
+    fs::write(key_dir.join("public.key"), pubkey.to_armored_string(None)?)
+        .context("Failed to write public key")?;
+    if let Some(seckey) = seckey {
+        write_private(
+            &key_dir.join("private.key"),
+            &seckey.to_armored_string(None)?,
+        )
+        .context("Failed to write private key")?;
+    }
 
-pub fn decrypt(
-    armored: &str,
-    seckey: &SignedSecretKey,
-    password: String,
-) -> Result<String, anyhow::Error> {
-    let buf = Cursor::new(armored);
-    let (msg, _) = composed::message::Message::from_armor_single(buf)
-        .context("Failed to convert &str to armored message")?;
-    let (mut decryptor, _) = msg
-        .decrypt(|| password, &[seckey])
-        .context("Decrypting the message")?;
-
-    if let Some(msg) = decryptor.next() {
-        let bytes = msg?.get_content()?.context("Failed to get content")?;
-        let clear_text = String::from_utf8(bytes)?;
-        return Ok(clear_text);
+    if let Some(existing) = config
+        .keys
+        .iter_mut()
+        .find(|k| k.fingerprint == fingerprint)
+    {
+        if seckey.is_some() && existing.is_pubkey_only() {
+            existing.pubkey_only = None;
+            println!("Added the secret key to {}", existing);
+        } else {
+            println!("{} was already imported", existing);
+        }
+        return Ok(());
     }
 
-    Err(anyhow::Error::msg("Failed to find message"))
+    let (primary_user_id, hashed_note) = owner()?;
+    config.keys.push(Key {
+        fingerprint,
+        note: "".to_string(),
+        pubkey_only: seckey.is_none().then_some(true),
+        primary_user_id,
+        hashed_note,
+        uuid: None,
+    });
+
+    Ok(())
 }
 
 pub fn hash_string(input: &str) -> String {
@@ -145,23 +120,7 @@ pub fn decrypt_full(message: String, config: &Config) -> Result<String, anyhow::
         .map(|e| e.encode_hex_upper())
         .collect();
 
-    let keyring = config
-        .keys
-        .iter()
-        .map(|k| k.fingerprint.clone())
-        .collect::<Vec<String>>();
-
-    let available_keys: Vec<String> = keyring
-        .iter()
-        .filter(|&keyring_key| {
-            recipients.iter().any(|recipient_key| {
-                keyring_key
-                    .to_lowercase()
-                    .contains(&recipient_key.to_lowercase())
-            })
-        })
-        .cloned()
-        .collect();
+    let available_keys = available_keys(&recipients, config);
 
     if available_keys.is_empty() {
         return Err(anyhow::anyhow!(
@@ -174,7 +133,7 @@ pub fn decrypt_full(message: String, config: &Config) -> Result<String, anyhow::
     let (key, fingerprint) = if available_keys.iter().any(|k| k.contains(primary_key)) {
         get_key(primary_key)?
     } else {
-        println!("Using key: {}", &available_keys[0]);
+        eprintln!("Using key: {}", &available_keys[0]);
         get_key(&available_keys[0])?
     };
 
@@ -189,7 +148,10 @@ pub fn decrypt_full_many(
     messages: Vec<String>,
     config: &Config,
 ) -> Result<Vec<String>, anyhow::Error> {
-    let first = messages.first().ok_or_else(|| anyhow!("No messages"))?;
+    // Nothing to decrypt, don't prompt for a passphrase
+    let Some(first) = messages.first() else {
+        return Ok(vec![]);
+    };
     let msg = Message::from_string(first.as_str())?.0;
 
     let recipients: Vec<String> = msg
@@ -198,23 +160,7 @@ pub fn decrypt_full_many(
         .map(|e| e.encode_hex_upper())
         .collect();
 
-    let keyring: Vec<String> = config
-        .keys
-        .iter()
-        .map(|k| k.fingerprint.clone())
-        .collect::<Vec<String>>();
-
-    let available_keys: Vec<String> = keyring
-        .iter()
-        .filter(|&keyring_key| {
-            recipients.iter().any(|recipient_key| {
-                keyring_key
-                    .to_lowercase()
-                    .contains(&recipient_key.to_lowercase())
-            })
-        })
-        .cloned()
-        .collect();
+    let available_keys = available_keys(&recipients, config);
 
     if available_keys.is_empty() {
         return Err(anyhow::anyhow!(
@@ -227,7 +173,7 @@ pub fn decrypt_full_many(
     let (key, fingerprint) = if available_keys.iter().any(|k| k.contains(primary_key)) {
         get_key(primary_key)?
     } else {
-        println!("Using key: {}", &available_keys[0]);
+        eprintln!("Using key: {}", &available_keys[0]);
         get_key(&available_keys[0])?
     };
 
@@ -236,11 +182,44 @@ pub fn decrypt_full_many(
     let decrypted = messages
         .par_iter()
         .map(|m| decrypt(m.as_str(), &key, passphrase.clone()))
-        .collect::<Result<Vec<String>, anyhow::Error>>()?;
+        .collect::<Result<Vec<String>, envx::error::Error>>()?;
 
     Ok(decrypted)
 }
 
+/// Fingerprints of the configured keys a message to `recipients` can be decrypted with
+///
+/// Recipients are key IDs, the end of the fingerprint of the primary key or, for Ed25519
+/// keys, of the encryption subkey
+fn available_keys(recipients: &[String], config: &Config) -> Vec<String> {
+    let is_recipient = |fingerprint: &str| {
+        recipients.iter().any(|recipient_key| {
+            fingerprint
+                .to_lowercase()
+                .contains(&recipient_key.to_lowercase())
+        })
+    };
+
+    config
+        .keys
+        .iter()
+        .filter(|key| !key.is_pubkey_only())
+        .filter(|key| {
+            is_recipient(&key.fingerprint)
+                || key
+                    .public_key()
+                    .ok()
+                    .and_then(|k| SignedPublicKey::from_string(&k).ok())
+                    .is_some_and(|(k, _)| {
+                        k.public_subkeys
+                            .iter()
+                            .any(|s| is_recipient(&s.fingerprint().to_hex()))
+                    })
+        })
+        .map(|k| k.fingerprint.clone())
+        .collect()
+}
+
 /// Get the key from the keyring
 ///
 /// Returns (Key, fingerprint)
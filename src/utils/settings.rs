@@ -1,14 +1,66 @@
+use envx::http::HttpOptions;
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Settings {
     pub warn_on_short_passwords: bool,
+
+    /// Seconds to wait for a connection to the server
+    #[serde(default = "default_connect_timeout")]
+    pub connect_timeout: u64,
+
+    /// Seconds a whole request may take
+    #[serde(default = "default_request_timeout")]
+    pub request_timeout: u64,
+
+    /// How many times requests that are safe to repeat are retried
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+
+    /// Keep signed auth tokens on disk so the next commands can reuse them
+    #[serde(default)]
+    pub cache_tokens: bool,
 }
 
-impl Settings {
-    pub fn default() -> Self {
+impl Default for Settings {
+    fn default() -> Self {
         Settings {
             warn_on_short_passwords: false,
+            connect_timeout: default_connect_timeout(),
+            request_timeout: default_request_timeout(),
+            max_retries: default_max_retries(),
+            cache_tokens: false,
         }
     }
 }
+
+impl Settings {
+    /// A timeout of 0, e.g. from a config edited by hand, would fail every request, so
+    /// it falls back to the default
+    pub fn http_options(&self) -> HttpOptions {
+        let defaults = HttpOptions::default();
+        let seconds = |value: u64, default: Duration| match value {
+            0 => default,
+            value => Duration::from_secs(value),
+        };
+
+        HttpOptions {
+            connect_timeout: seconds(self.connect_timeout, defaults.connect_timeout),
+            request_timeout: seconds(self.request_timeout, defaults.request_timeout),
+            max_retries: self.max_retries,
+        }
+    }
+}
+
+fn default_connect_timeout() -> u64 {
+    HttpOptions::default().connect_timeout.as_secs()
+}
+
+fn default_request_timeout() -> u64 {
+    HttpOptions::default().request_timeout.as_secs()
+}
+
+fn default_max_retries() -> u32 {
+    HttpOptions::default().max_retries
+}
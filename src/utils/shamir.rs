@@ -0,0 +1,406 @@
+// Shamir secret sharing over GF(256), and the printable shares of `envx key split`
+//
+// Every byte of the secret is the constant term of its own random polynomial of degree
+// threshold - 1, share x holds the value of every polynomial at x
+
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+use crypto_hash::{hex_digest, Algorithm};
+use rand::RngCore;
+use std::fmt::Display;
+
+/// Format of a share, bump it when the format changes
+pub const SHARE_VERSION: u64 = 1;
+
+const BEGIN: &str = "-----BEGIN ENVX KEY SHARE-----";
+const END: &str = "-----END ENVX KEY SHARE-----";
+
+/// Multiplication in GF(2^8) with the AES polynomial x^8 + x^4 + x^3 + x + 1
+fn mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0;
+    while b != 0 {
+        if b & 1 != 0 {
+            product ^= a;
+        }
+        let carry = a & 0x80 != 0;
+        a <<= 1;
+        if carry {
+            a ^= 0x1b;
+        }
+        b >>= 1;
+    }
+    product
+}
+
+/// a^254 = a^-1, as every non-zero a has a^255 = 1
+fn inv(a: u8) -> u8 {
+    let mut result = 1;
+    for _ in 0..254 {
+        result = mul(result, a);
+    }
+    result
+}
+
+/// Split a secret into `shares` shares, any `threshold` of which rebuild it
+///
+/// Returns (x, share) pairs, x goes from 1 to `shares`
+pub fn split(secret: &[u8], shares: u8, threshold: u8) -> Result<Vec<(u8, Vec<u8>)>> {
+    if threshold < 2 {
+        bail!("The threshold must be at least 2");
+    }
+    if threshold > shares {
+        bail!("The threshold can't be more than the number of shares");
+    }
+
+    let mut rng = rand::thread_rng();
+    let mut out = (1..=shares)
+        .map(|x| (x, Vec::with_capacity(secret.len())))
+        .collect::<Vec<(u8, Vec<u8>)>>();
+
+    let mut coefficients = vec![0u8; threshold as usize];
+    for byte in secret {
+        coefficients[0] = *byte;
+        rng.fill_bytes(&mut coefficients[1..]);
+
+        for (x, share) in out.iter_mut() {
+            // Horner's method, from the highest degree down
+            let y = coefficients.iter().rev().fold(0, |acc, c| mul(acc, *x) ^ c);
+            share.push(y);
+        }
+    }
+
+    Ok(out)
+}
+
+/// Rebuild a secret from (x, share) pairs, with Lagrange interpolation at 0
+///
+/// Fewer shares than the threshold give garbage, not an error
+pub fn combine(shares: &[(u8, Vec<u8>)]) -> Result<Vec<u8>> {
+    let Some((_, first)) = shares.first() else {
+        bail!("No shares to combine");
+    };
+    if shares
+        .iter()
+        .any(|(x, s)| *x == 0 || s.len() != first.len())
+    {
+        bail!("The shares don't belong together");
+    }
+    for (i, (x, _)) in shares.iter().enumerate() {
+        if shares[..i].iter().any(|(other, _)| other == x) {
+            bail!("Share {} was given twice", x);
+        }
+    }
+
+    // Basis polynomials at 0: product of x_j / (x_j - x_i), subtraction is xor
+    let basis = shares
+        .iter()
+        .map(|(xi, _)| {
+            shares
+                .iter()
+                .filter(|(xj, _)| xj != xi)
+                .fold(1, |acc, (xj, _)| mul(acc, mul(*xj, inv(xj ^ xi))))
+        })
+        .collect::<Vec<u8>>();
+
+    Ok((0..first.len())
+        .map(|i| {
+            shares
+                .iter()
+                .zip(&basis)
+                .fold(0, |acc, ((_, share), l)| acc ^ mul(share[i], *l))
+        })
+        .collect())
+}
+
+/// One printable share of a secret key
+#[derive(Debug, Clone)]
+pub struct Share {
+    pub fingerprint: String,
+    /// Random ID of the split, shares of different splits of a key don't combine
+    pub split_id: String,
+    pub index: u8,
+    pub shares: u8,
+    pub threshold: u8,
+    /// User ID of the key, to register it in the config once rebuilt
+    pub user_id: String,
+    pub data: Vec<u8>,
+}
+
+impl Share {
+    /// Everything but the data itself, shares of one split all have the same
+    pub fn same_split(&self, other: &Share) -> bool {
+        self.fingerprint == other.fingerprint
+            && self.split_id == other.split_id
+            && self.threshold == other.threshold
+            && self.shares == other.shares
+    }
+
+    fn checksum(&self) -> String {
+        let hash = hex_digest(
+            Algorithm::SHA256,
+            format!(
+                "{}:{}:{}:{}:{}:{}:{}:{}",
+                SHARE_VERSION,
+                self.fingerprint,
+                self.split_id,
+                self.index,
+                self.shares,
+                self.threshold,
+                self.user_id,
+                BASE64.encode(&self.data)
+            )
+            .as_bytes(),
+        );
+        hash[..16].to_string()
+    }
+
+    /// Every share in a text, e.g. several files or a paste
+    pub fn parse_all(text: &str) -> Result<Vec<Share>> {
+        // Pasted from Windows
+        let text = text.replace("\r\n", "\n");
+
+        let mut shares = vec![];
+        let mut rest = text.as_str();
+        while let Some(start) = rest.find(BEGIN) {
+            let block = &rest[start + BEGIN.len()..];
+            let end = block.find(END).context("A share is missing its END line")?;
+            shares.push(Share::parse(&block[..end])?);
+            rest = &block[end + END.len()..];
+        }
+        Ok(shares)
+    }
+
+    /// The lines between BEGIN and END: headers, a blank line, then base64
+    fn parse(block: &str) -> Result<Share> {
+        let (headers, body) = block
+            .trim()
+            .split_once("\n\n")
+            .context("A share is missing its data")?;
+
+        let headers = headers
+            .lines()
+            .filter_map(|l| l.split_once(':'))
+            .map(|(k, v)| (k.trim(), v.trim()))
+            .collect::<Vec<(&str, &str)>>();
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(k, _)| *k == name)
+                .map(|(_, v)| *v)
+                .with_context(|| format!("A share is missing its {} header", name))
+        };
+
+        let version = header("Version")?
+            .parse::<u64>()
+            .context("Invalid share version")?;
+        if version > SHARE_VERSION {
+            bail!("The share was made by a newer version of envx, update envx first");
+        }
+
+        // "2/5, threshold 3"
+        let share = header("Share")?;
+        let (position, threshold) = share
+            .split_once(", threshold ")
+            .context("Invalid Share header")?;
+        let (index, shares) = position.split_once('/').context("Invalid Share header")?;
+
+        let data = body.split_whitespace().collect::<String>();
+        let parsed = Share {
+            fingerprint: header("Fingerprint")?.to_string(),
+            split_id: header("Split")?.to_string(),
+            index: index.trim().parse().context("Invalid share index")?,
+            shares: shares.trim().parse().context("Invalid share count")?,
+            threshold: threshold.trim().parse().context("Invalid threshold")?,
+            user_id: header("User")?.to_string(),
+            data: BASE64.decode(data).context("Invalid share data")?,
+        };
+
+        if parsed.checksum() != header("Checksum")? {
+            bail!(
+                "Share {} of key {} is damaged, its checksum doesn't match",
+                parsed.index,
+                parsed.fingerprint
+            );
+        }
+        Ok(parsed)
+    }
+}
+
+impl Display for Share {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", BEGIN)?;
+        writeln!(f, "Version: {}", SHARE_VERSION)?;
+        writeln!(f, "Fingerprint: {}", self.fingerprint)?;
+        writeln!(f, "User: {}", self.user_id)?;
+        writeln!(f, "Split: {}", self.split_id)?;
+        writeln!(
+            f,
+            "Share: {}/{}, threshold {}",
+            self.index, self.shares, self.threshold
+        )?;
+        writeln!(f, "Checksum: {}", self.checksum())?;
+        writeln!(f)?;
+
+        let data = BASE64.encode(&self.data);
+        for line in data.as_bytes().chunks(64) {
+            writeln!(f, "{}", String::from_utf8_lossy(line))?;
+        }
+        write!(f, "{}", END)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Every subset of `size` indexes of `0..n`
+    fn subsets(n: usize, size: usize) -> Vec<Vec<usize>> {
+        (0u32..1 << n)
+            .filter(|mask| mask.count_ones() as usize == size)
+            .map(|mask| (0..n).filter(|i| mask & (1 << i) != 0).collect())
+            .collect()
+    }
+
+    fn share(data: Vec<u8>) -> Share {
+        Share {
+            fingerprint: "0123456789ABCDEF0123456789ABCDEF01234567".to_string(),
+            split_id: "5f3a9c1e".to_string(),
+            index: 2,
+            shares: 5,
+            threshold: 3,
+            user_id: "Alice <alice@example.com>".to_string(),
+            data,
+        }
+    }
+
+    #[test]
+    fn field_identities() {
+        for a in 1..=255u8 {
+            assert_eq!(mul(a, 1), a);
+            assert_eq!(mul(a, 0), 0);
+            assert_eq!(mul(a, inv(a)), 1, "inverse of {}", a);
+            for b in 1..=255u8 {
+                assert_eq!(mul(a, b), mul(b, a));
+                assert_ne!(mul(a, b), 0);
+            }
+        }
+        // From FIPS-197
+        assert_eq!(mul(0x57, 0x83), 0xc1);
+    }
+
+    #[test]
+    fn every_threshold_subset_rebuilds_the_secret() {
+        let secret = (0..=255u8).chain(*b"-----BEGIN PGP").collect::<Vec<u8>>();
+        let shares = split(&secret, 5, 3).unwrap();
+
+        for size in 3..=5 {
+            for subset in subsets(5, size) {
+                let picked = subset
+                    .iter()
+                    .map(|i| shares[*i].clone())
+                    .collect::<Vec<(u8, Vec<u8>)>>();
+                assert_eq!(combine(&picked).unwrap(), secret, "shares {:?}", subset);
+            }
+        }
+    }
+
+    #[test]
+    fn below_threshold_gives_something_else() {
+        let secret = b"a private key, long enough that a match by chance can't happen".to_vec();
+        let shares = split(&secret, 5, 3).unwrap();
+
+        for subset in subsets(5, 2) {
+            let picked = subset
+                .iter()
+                .map(|i| shares[*i].clone())
+                .collect::<Vec<(u8, Vec<u8>)>>();
+            assert_ne!(combine(&picked).unwrap(), secret, "shares {:?}", subset);
+        }
+    }
+
+    #[test]
+    fn invalid_splits_and_combinations() {
+        assert!(split(b"secret", 5, 1).is_err());
+        assert!(split(b"secret", 2, 3).is_err());
+        assert!(combine(&[]).is_err());
+
+        let shares = split(b"secret", 3, 2).unwrap();
+        assert!(combine(&[shares[0].clone(), shares[0].clone()]).is_err());
+        assert!(combine(&[shares[0].clone(), (2, vec![1, 2, 3])]).is_err());
+    }
+
+    #[test]
+    fn display_parse_round_trip() {
+        let original = share((0..200).map(|i| (i * 7) as u8).collect());
+        let parsed = Share::parse_all(&original.to_string()).unwrap();
+
+        assert_eq!(parsed.len(), 1);
+        let parsed = &parsed[0];
+        assert!(parsed.same_split(&original));
+        assert_eq!(parsed.index, original.index);
+        assert_eq!(parsed.user_id, original.user_id);
+        assert_eq!(parsed.data, original.data);
+    }
+
+    #[test]
+    fn parse_all_reads_several_shares_and_crlf() {
+        let first = share(vec![1, 2, 3]);
+        let second = Share {
+            index: 4,
+            ..share(vec![4, 5, 6])
+        };
+        let text = format!("Share one\n{}\n\nShare two\n{}\n", first, second).replace('\n', "\r\n");
+
+        let parsed = Share::parse_all(&text).unwrap();
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[1].index, 4);
+        assert_eq!(parsed[1].data, vec![4, 5, 6]);
+    }
+
+    #[test]
+    fn checksum_mismatch_is_detected() {
+        let text = share(vec![42; 48]).to_string();
+
+        // Changed data
+        let data_line = text.lines().nth(8).unwrap();
+        let flipped = data_line.replacen('q', "r", 1).replacen('A', "B", 1);
+        assert_ne!(data_line, flipped);
+        let tampered = text.replacen(data_line, &flipped, 1);
+        let err = Share::parse_all(&tampered).unwrap_err();
+        assert!(err.to_string().contains("checksum"), "{}", err);
+
+        // Changed header
+        let tampered = text.replace("Share: 2/5", "Share: 3/5");
+        assert!(Share::parse_all(&tampered).is_err());
+
+        // Missing checksum
+        let stripped = text
+            .lines()
+            .filter(|l| !l.starts_with("Checksum"))
+            .collect::<Vec<&str>>()
+            .join("\n");
+        assert!(Share::parse_all(&stripped).is_err());
+    }
+
+    #[test]
+    fn shares_of_other_splits_dont_match() {
+        let original = share(vec![1, 2, 3]);
+        let other_split = Share {
+            split_id: "0badc0de".to_string(),
+            ..original.clone()
+        };
+        let other_threshold = Share {
+            threshold: 2,
+            ..original.clone()
+        };
+
+        // Both are intact shares on their own
+        let parsed = Share::parse_all(&format!("{}\n{}", other_split, other_threshold)).unwrap();
+        assert!(!original.same_split(&parsed[0]));
+        assert!(!original.same_split(&parsed[1]));
+        assert!(original.same_split(&Share {
+            index: 5,
+            ..original.clone()
+        }));
+    }
+}
@@ -0,0 +1,279 @@
+use anyhow::{bail, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use std::{collections::BTreeMap, iter::Peekable, str::CharIndices};
+
+/// Fill `{{ KEY }}` placeholders in a template
+///
+/// Placeholders can go through filters, e.g. `{{ KEY | default("x") | base64 }}`:
+/// - `default("x")` is used when KEY is missing or empty
+/// - `base64` encodes the value
+/// - `json_escape` escapes the value for use inside a JSON string
+///
+/// `\{{` is written as a literal `{{`. Every unknown key is reported at once.
+pub fn render(template: &str, variables: &BTreeMap<String, String>) -> Result<String> {
+    let mut output = String::with_capacity(template.len());
+    let mut errors = vec![];
+    let mut rest = template;
+
+    while let Some(i) = rest.find("{{") {
+        if rest[..i].ends_with('\\') {
+            output.push_str(&rest[..i - 1]);
+            output.push_str("{{");
+            rest = &rest[i + 2..];
+            continue;
+        }
+        output.push_str(&rest[..i]);
+
+        let offset = template.len() - rest.len() + i;
+        let line = template[..offset].matches('\n').count() + 1;
+
+        let end = closing_braces(&rest[i + 2..])
+            .with_context(|| format!("Unterminated placeholder on line {}", line))?;
+        let expression = &rest[i + 2..i + 2 + end];
+        rest = &rest[i + 2 + end + 2..];
+
+        match Placeholder::parse(expression)
+            .with_context(|| format!("Invalid placeholder `{{{{{}}}}}`", expression))
+            .and_then(|p| p.evaluate(variables))
+        {
+            Ok(value) => output.push_str(&value),
+            Err(e) => errors.push(format!("line {}: {:#}", line, e)),
+        }
+    }
+    output.push_str(rest);
+
+    if !errors.is_empty() {
+        bail!(
+            "{} placeholder(s) could not be filled\n  {}",
+            errors.len(),
+            errors.join("\n  ")
+        );
+    }
+
+    Ok(output)
+}
+
+/// Index of the `}}` closing a placeholder, ignoring braces in quoted strings
+fn closing_braces(s: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut chars = s.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match (quote, c) {
+            (Some(_), '\\') => {
+                chars.next();
+            }
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '}') if s[i + 1..].starts_with('}') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+enum Filter {
+    Default(String),
+    Base64,
+    JsonEscape,
+}
+
+struct Placeholder {
+    key: String,
+    filters: Vec<Filter>,
+}
+
+impl Placeholder {
+    fn parse(expression: &str) -> Result<Self> {
+        let mut chars = expression.char_indices().peekable();
+
+        let key = identifier(expression, &mut chars).context("Expected a key")?;
+        let mut filters = vec![];
+
+        loop {
+            skip_whitespace(&mut chars);
+            match chars.next() {
+                None => break,
+                Some((_, '|')) => {}
+                Some((_, c)) => bail!("Unexpected `{}`, expected `|`", c),
+            }
+
+            let name = identifier(expression, &mut chars).context("Expected a filter name")?;
+            let filter = match name.as_str() {
+                "default" => {
+                    skip_whitespace(&mut chars);
+                    if chars.next().map(|(_, c)| c) != Some('(') {
+                        bail!("Expected `(` after default");
+                    }
+                    skip_whitespace(&mut chars);
+                    let value = string(&mut chars)?;
+                    skip_whitespace(&mut chars);
+                    if chars.next().map(|(_, c)| c) != Some(')') {
+                        bail!("Expected `)` after the default value");
+                    }
+                    Filter::Default(value)
+                }
+                "base64" => Filter::Base64,
+                "json_escape" => Filter::JsonEscape,
+                other => bail!("Unknown filter `{}`", other),
+            };
+            filters.push(filter);
+        }
+
+        Ok(Self { key, filters })
+    }
+
+    fn evaluate(&self, variables: &BTreeMap<String, String>) -> Result<String> {
+        let mut value = variables.get(&self.key).cloned();
+
+        for filter in self.filters.iter() {
+            value = match (filter, value) {
+                (Filter::Default(default), None) => Some(default.clone()),
+                (Filter::Default(default), Some(v)) if v.is_empty() => Some(default.clone()),
+                (Filter::Base64, Some(v)) => Some(STANDARD.encode(v)),
+                (Filter::JsonEscape, Some(v)) => {
+                    let quoted = serde_json::to_string(&v)?;
+                    Some(quoted[1..quoted.len() - 1].to_string())
+                }
+                (_, v) => v,
+            };
+        }
+
+        value.with_context(|| {
+            format!(
+                "Unknown key {}, set it or use `{{{{ {} | default(\"...\") }}}}`",
+                self.key, self.key
+            )
+        })
+    }
+}
+
+fn skip_whitespace(chars: &mut Peekable<CharIndices>) {
+    while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+}
+
+fn identifier(expression: &str, chars: &mut Peekable<CharIndices>) -> Option<String> {
+    skip_whitespace(chars);
+    let start = chars.peek()?.0;
+    let mut end = start;
+    while let Some((i, c)) = chars.next_if(|(_, c)| c.is_ascii_alphanumeric() || *c == '_') {
+        end = i + c.len_utf8();
+    }
+    (end > start).then(|| expression[start..end].to_string())
+}
+
+/// A single or double quoted string, with backslash escapes
+fn string(chars: &mut Peekable<CharIndices>) -> Result<String> {
+    let quote = match chars.next() {
+        Some((_, c @ ('"' | '\''))) => c,
+        _ => bail!("Expected a quoted string"),
+    };
+
+    let mut value = String::new();
+    loop {
+        match chars.next() {
+            None => bail!("Unterminated string"),
+            Some((_, c)) if c == quote => return Ok(value),
+            Some((_, '\\')) => match chars.next() {
+                Some((_, 'n')) => value.push('\n'),
+                Some((_, 't')) => value.push('\t'),
+                Some((_, c)) => value.push(c),
+                None => bail!("Unterminated string"),
+            },
+            Some((_, c)) => value.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variables() -> BTreeMap<String, String> {
+        [
+            ("HOST", "db.internal"),
+            ("EMPTY", ""),
+            ("QUOTE", "say \"hi\"\n"),
+        ]
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+    }
+
+    fn ok(template: &str) -> String {
+        render(template, &variables()).unwrap()
+    }
+
+    fn err(template: &str) -> String {
+        format!("{:#}", render(template, &variables()).unwrap_err())
+    }
+
+    #[test]
+    fn placeholders() {
+        assert_eq!(ok("host={{HOST}}"), "host=db.internal");
+        assert_eq!(ok("host={{   HOST  }}:5432"), "host=db.internal:5432");
+        assert_eq!(ok("{{HOST}}{{EMPTY}}{{HOST}}"), "db.internaldb.internal");
+        assert_eq!(ok("no placeholders } here"), "no placeholders } here");
+    }
+
+    #[test]
+    fn filters() {
+        assert_eq!(ok("{{ MISSING | default(\"x\") }}"), "x");
+        assert_eq!(ok("{{ EMPTY | default('x') }}"), "x");
+        assert_eq!(ok("{{ HOST | default(\"x\") }}"), "db.internal");
+        assert_eq!(ok("{{ HOST|base64 }}"), "ZGIuaW50ZXJuYWw=");
+        assert_eq!(ok("{{ QUOTE | json_escape }}"), "say \\\"hi\\\"\\n");
+    }
+
+    #[test]
+    fn chained_filters_apply_in_order() {
+        assert_eq!(ok("{{ MISSING | default(\"a\") | base64 }}"), "YQ==");
+        assert_eq!(ok("{{ EMPTY | base64 | default(\"a\") }}"), "a");
+        assert_eq!(
+            ok("{{ MISSING | default(\"\\\"q\\\"\") | json_escape | base64 }}"),
+            STANDARD.encode("\\\"q\\\"")
+        );
+    }
+
+    #[test]
+    fn default_values_may_hold_braces_and_pipes() {
+        assert_eq!(ok("{{ MISSING | default(\"}} | {{\") }}"), "}} | {{");
+        assert_eq!(ok("{{ MISSING | default('it\\'s') }}"), "it's");
+        assert_eq!(ok("{{ MISSING | default(\"a\\nb\") }}"), "a\nb");
+    }
+
+    #[test]
+    fn escaped_braces() {
+        assert_eq!(ok("\\{{ HOST }}"), "{{ HOST }}");
+        assert_eq!(ok("\\{{HOST}} is {{HOST}}"), "{{HOST}} is db.internal");
+        assert_eq!(ok("}} {"), "}} {");
+    }
+
+    #[test]
+    fn unknown_filters_and_syntax_errors() {
+        let e = err("{{ HOST | upper }}");
+        assert!(e.contains("Unknown filter `upper`"), "{}", e);
+        assert!(e.contains("line 1"), "{}", e);
+
+        assert!(err("{{ HOST | default(x) }}").contains("Expected a quoted string"));
+        assert!(err("{{ HOST | default(\"x\" }}").contains("Expected `)`"));
+        assert!(err("{{ HOST HOST }}").contains("expected `|`"));
+        assert!(err("{{ }}").contains("Expected a key"));
+        assert!(err("{{ HOST | }}").contains("Expected a filter name"));
+        assert!(err("a\n{{ HOST").contains("Unterminated placeholder on line 2"));
+    }
+
+    #[test]
+    fn every_unknown_key_is_reported_at_once() {
+        let e = err("{{ ONE }}\n{{ HOST }}\n{{ TWO | base64 }}\n{{ HOST | nope }}");
+
+        assert!(
+            e.starts_with("3 placeholder(s) could not be filled"),
+            "{}",
+            e
+        );
+        assert!(e.contains("line 1: Unknown key ONE"), "{}", e);
+        assert!(e.contains("line 3: Unknown key TWO"), "{}", e);
+        assert!(e.contains("line 4: Invalid placeholder"), "{}", e);
+    }
+}
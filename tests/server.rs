@@ -0,0 +1,204 @@
+//! envx-server driven through `envx::Client`, on an ephemeral port
+//!
+//! Run with `cargo test --features server`
+
+use envx::{
+    client::{Client, Identity, Passphrase},
+    crypto::{generate_key_pair, KeyAlgorithm},
+    error::Error,
+    http::Http,
+    kvpair::KVPair,
+    server::{
+        routes,
+        store::{Project, Store},
+    },
+};
+use std::{net::SocketAddr, path::PathBuf, sync::Arc};
+
+const PASSPHRASE: &str = "correct horse battery staple";
+
+/// A data file of its own for every test
+fn data_file(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "envx-server-test-{}-{}-{}",
+        name,
+        std::process::id(),
+        rand::random::<u32>()
+    ));
+    dir.join("data.json")
+}
+
+async fn start(data: PathBuf) -> String {
+    let store = Arc::new(Store::open(data).unwrap());
+    let address = "127.0.0.1:0".parse::<SocketAddr>().unwrap();
+    let server = axum::Server::bind(&address).serve(routes::router(store).into_make_service());
+    let url = format!("http://{}", server.local_addr());
+
+    tokio::spawn(server);
+    url
+}
+
+/// Register a new key on the server and return a client for it
+async fn user(url: &str, name: &str) -> Client {
+    let key_pair = generate_key_pair(
+        format!("{} <{}@example.com>", name, name.to_lowercase()),
+        PASSPHRASE.to_string(),
+        KeyAlgorithm::Ed25519,
+    )
+    .unwrap();
+    let public_key = key_pair.public_key.to_armored_string(None).unwrap();
+    let secret_key = key_pair.secret_key.to_armored_string(None).unwrap();
+
+    let user_id = Client::new_user(&Http::default(), url, name, &public_key)
+        .await
+        .unwrap();
+    let identity = Identity::new(user_id, &secret_key).unwrap();
+
+    Client::new(url, identity, Passphrase::Static(PASSPHRASE.into())).unwrap()
+}
+
+fn kvpair(key: &str, value: &str) -> KVPair {
+    KVPair::new(key.to_string(), value.to_string())
+}
+
+#[tokio::test]
+async fn share_a_project_and_remove_a_member() {
+    let data = data_file("share");
+    let url = start(data.clone()).await;
+
+    let alice = user(&url, "Alice").await;
+    let bob = user(&url, "Bob").await;
+    assert!(alice.test_auth().await.unwrap().contains("Alice"));
+
+    let project_id = alice.new_project().await.unwrap();
+    alice
+        .add_user_to_project(&bob.identity().user_id, &project_id)
+        .await
+        .unwrap();
+
+    let info = alice.project_info(&project_id).await.unwrap();
+    assert_eq!(info.users.len(), 2);
+
+    let ids = alice
+        .set_many(
+            &[
+                kvpair("DATABASE_URL", "postgres://db"),
+                kvpair("TOKEN", "1"),
+            ],
+            &project_id,
+        )
+        .await
+        .unwrap();
+    assert_eq!(ids.len(), 2);
+    bob.set_many(&[kvpair("TOKEN", "2")], &project_id)
+        .await
+        .unwrap();
+
+    // Every version, readable by both members
+    let versions = alice.variables(&project_id).await.unwrap();
+    assert_eq!(versions.len(), 3);
+    let pruned = bob.variables_pruned(&project_id).await.unwrap();
+    let pruned = pruned
+        .iter()
+        .map(|k| (k.key.as_str(), k.value.as_str()))
+        .collect::<Vec<(&str, &str)>>();
+    assert_eq!(pruned, [("DATABASE_URL", "postgres://db"), ("TOKEN", "2")]);
+
+    alice
+        .remove_user_from_project(&bob.identity().user_id, &project_id)
+        .await
+        .unwrap();
+    assert!(matches!(
+        bob.variables_encrypted(&project_id).await,
+        Err(Error::NotFound(_))
+    ));
+    assert_eq!(
+        alice.project_info(&project_id).await.unwrap().users.len(),
+        1
+    );
+
+    // The last member can't be removed
+    assert!(alice
+        .remove_user_from_project(&alice.identity().user_id, &project_id)
+        .await
+        .is_err());
+
+    // Everything was written to the data file
+    let reopened = Store::open(data.clone()).unwrap();
+    let reopened = reopened.lock();
+    assert_eq!(reopened.users.len(), 2);
+    assert_eq!(reopened.variables.len(), 3);
+    assert_eq!(
+        reopened.project(&project_id).unwrap().members,
+        [alice.identity().user_id.clone()]
+    );
+
+    let _ = std::fs::remove_dir_all(data.parent().unwrap());
+}
+
+#[tokio::test]
+async fn rejects_outsiders_and_bad_tokens() {
+    let data = data_file("outsiders");
+    let url = start(data.clone()).await;
+
+    let alice = user(&url, "Alice").await;
+    let mallory = user(&url, "Mallory").await;
+
+    let project_id = alice.new_project().await.unwrap();
+    alice
+        .set_many(&[kvpair("TOKEN", "1")], &project_id)
+        .await
+        .unwrap();
+
+    assert!(matches!(
+        mallory.variables_encrypted(&project_id).await,
+        Err(Error::NotFound(_))
+    ));
+    assert!(mallory
+        .set_many(&[kvpair("TOKEN", "2")], &project_id)
+        .await
+        .is_err());
+    assert!(mallory.list_projects().await.unwrap().is_empty());
+
+    // Mallory's key signing for Alice's user
+    let impostor = Client::new(
+        &url,
+        Identity {
+            user_id: alice.identity().user_id.clone(),
+            secret_key: mallory.identity().secret_key.clone(),
+        },
+        Passphrase::Static(PASSPHRASE.into()),
+    )
+    .unwrap();
+    assert!(matches!(
+        impostor.list_projects().await,
+        Err(Error::Unauthorized(_))
+    ));
+
+    let _ = std::fs::remove_dir_all(data.parent().unwrap());
+}
+
+#[test]
+fn failed_saves_leave_the_data_unchanged() {
+    let data = data_file("unsaved");
+    let dir = data.parent().unwrap();
+    std::fs::create_dir_all(dir).unwrap();
+
+    // A file where the data file's directory should be
+    let blocker = dir.join("blocker");
+    std::fs::write(&blocker, "").unwrap();
+    let store = Store::open(blocker.join("data.json")).unwrap();
+
+    let result = store.update(|data| {
+        data.projects.push(Project {
+            id: "project".into(),
+            created_at: "now".into(),
+            members: vec!["alice".into()],
+        });
+        Ok::<(), anyhow::Error>(())
+    });
+    assert!(result.is_err());
+    assert!(store.lock().projects.is_empty());
+
+    let _ = std::fs::remove_dir_all(dir);
+}